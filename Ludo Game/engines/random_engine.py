@@ -0,0 +1,41 @@
+#!/usr/bin/env python3
+"""Example engine for the Ludo engine protocol (see PROTOCOL.md).
+
+Plays a random legal move, but always takes a piece out of the yard when it can.
+Try it with:  cargo run --release -- simulate --bots "engine:python3 engines/random_engine.py,balanced"
+"""
+import random
+import sys
+
+
+def main():
+    position = None
+    roll = None
+    moves = []
+
+    for line in sys.stdin:
+        words = line.split()
+        if not words:
+            continue
+        command, args = words[0], words[1:]
+
+        if command == "ludo":
+            print("ready random_engine", flush=True)
+        elif command == "position":
+            position = args
+        elif command == "roll":
+            roll = int(args[0])
+        elif command == "moves":
+            moves = [int(piece) for piece in args]
+        elif command == "go":
+            to_move = int(position[1])
+            pieces = position[2 + to_move].split(",")
+            from_yard = [piece for piece in moves if pieces[piece] == "y"]
+            choice = from_yard[0] if roll == 6 and from_yard else random.choice(moves)
+            print(f"move {choice}", flush=True)
+        elif command == "quit":
+            break
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,130 @@
+use crate::board::{Board, PieceLocation, MAIN_TRACK_SPACES};
+use crate::bot::HeuristicBot;
+use crate::endgame;
+use crate::eval::Weights;
+use crate::player::{color_name, PlayerId};
+
+// A recommended move with the reasons for it
+pub struct Hint {
+    pub piece_idx: usize,
+    pub explanation: String,
+    // How much better the recommendation scores than each other option,
+    // as (piece, score difference), closest alternative first
+    pub margins: Vec<(usize, f64)>,
+    // True when the scores are exact win probabilities from the endgame
+    // tablebase rather than bot evaluation points
+    pub exact: bool,
+}
+
+// Ask the strongest bot we have which move it would play, and why. In
+// endgames covered by the tablebase that is the tablebase itself.
+pub fn hint(board: &Board, player_id: PlayerId, dice_value: u8, legal_moves: &[usize]) -> Hint {
+    let exact = endgame::default_tablebase().and_then(|tb| tb.rank_moves(board, player_id, dice_value, legal_moves));
+    let ranked = match &exact {
+        Some(ranked) => ranked.clone(),
+        None => HeuristicBot::new("Advisor".to_string(), Weights::default()).rank_moves(board, player_id, dice_value, legal_moves),
+    };
+    let (best_piece, best_score) = ranked[0];
+
+    Hint {
+        piece_idx: best_piece,
+        explanation: explain_move(board, player_id, best_piece, dice_value as usize),
+        margins: ranked[1..].iter().map(|&(piece_idx, score)| (piece_idx, best_score - score)).collect(),
+        exact: exact.is_some(),
+    }
+}
+
+// Describe in words what moving a piece would do, e.g.
+// "captures Blue's piece 2" or "escapes danger from Red at distance 3"
+pub fn explain_move(board: &Board, player_id: PlayerId, piece_idx: usize, steps: usize) -> String {
+    let from = board.get_piece_location(player_id, piece_idx);
+    let to = match board.destination(player_id, piece_idx, steps) {
+        Some(to) => to,
+        None => return "is not a legal move".to_string(),
+    };
+
+    let mut reasons = Vec::new();
+
+    match (from, to) {
+        (PieceLocation::Yard, _) => reasons.push(format!("brings piece {} out of the yard", piece_idx)),
+        (_, PieceLocation::Finished) => reasons.push(format!("brings piece {} home", piece_idx)),
+        (PieceLocation::MainTrack(_), PieceLocation::HomeTrack(_)) => {
+            reasons.push(format!("moves piece {} into the safety of the home track", piece_idx))
+        }
+        _ => {}
+    }
+
+    for victim in board.captures(player_id, piece_idx, steps) {
+        reasons.push(format!("captures {}'s piece {}", color_name(victim.player_id), victim.index));
+    }
+
+    if reasons.is_empty() {
+        let squares = match (from, to) {
+            (PieceLocation::MainTrack(a), PieceLocation::MainTrack(b)) => (b + MAIN_TRACK_SPACES - a) % MAIN_TRACK_SPACES,
+            _ => steps,
+        };
+        reasons.push(format!("advances piece {} by {} squares", piece_idx, squares));
+    }
+
+    // Compare the threats against this piece before and after the move
+    let threat = |board: &Board, location: PieceLocation| match location {
+        PieceLocation::MainTrack(pos) => board.attackers(player_id, pos).into_iter().min_by_key(|attacker| attacker.distance),
+        _ => None,
+    };
+    let mut after = board.clone();
+    after.move_piece(player_id, piece_idx, steps);
+    let before_threat = threat(board, from);
+    let after_threat = threat(&after, to);
+
+    match (before_threat, after_threat) {
+        (Some(attacker), None) => reasons.push(format!(
+            "escapes danger from {} at distance {}",
+            color_name(attacker.piece.player_id),
+            attacker.distance
+        )),
+        (_, Some(attacker)) => reasons.push(format!(
+            "but lands within reach of {} at distance {}",
+            color_name(attacker.piece.player_id),
+            attacker.distance
+        )),
+        _ => {}
+    }
+
+    reasons.join(", ")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::MoveResult;
+
+    #[test]
+    fn explains_captures() {
+        let board = Board::from_notation("t10yyy/t14t14yy 0 - classic").unwrap();
+        let explanation = explain_move(&board, 0, 0, 4);
+        let expected = format!("captures {}'s piece 0, captures {}'s piece 1", color_name(1), color_name(1));
+        assert!(explanation.starts_with(&expected), "{}", explanation);
+    }
+
+    // Leaving the yard never captures, even onto an occupied start square
+    #[test]
+    fn yard_exits_capture_nothing() {
+        let board = Board::from_notation("yyyy/t0yyy 0 - classic").unwrap();
+        let explanation = explain_move(&board, 0, 0, 6);
+        assert!(explanation.starts_with("brings piece 0 out of the yard"), "{}", explanation);
+        assert!(!explanation.contains("captures"), "{}", explanation);
+
+        let mut after = board.clone();
+        assert_eq!(after.move_piece(0, 0, 6), MoveResult::Moved);
+        assert_eq!(after.to_notation(), "t0yyy/t0yyy 0 - classic");
+    }
+
+    #[test]
+    fn explains_entering_the_home_track() {
+        let board = Board::from_notation("t45yyy/t30yyy 0 - classic").unwrap();
+        assert_eq!(board.destination(0, 0, 3), Some(PieceLocation::HomeTrack(1)));
+        let explanation = explain_move(&board, 0, 0, 3);
+        assert!(explanation.starts_with("moves piece 0 into the safety of the home track"), "{}", explanation);
+    }
+}
@@ -0,0 +1,234 @@
+use crate::board::{Board, MoveResult};
+use crate::player::PlayerId;
+use crate::record::TurnRecord;
+use crate::rollout;
+use crate::sim::default_threads;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::collections::HashMap;
+use std::thread;
+
+pub const DEFAULT_ROLLOUTS: usize = 100;
+// Moves that give away at least this much win probability are blunders
+pub const BLUNDER_THRESHOLD: f64 = 0.08;
+
+// How one turn looked to the engine, as win probabilities of the mover
+pub struct TurnReview {
+    pub turn: usize, // 1-based
+    pub player_id: PlayerId,
+    pub dice_value: u8,
+    pub before_roll: f64,
+    pub chosen: Option<(usize, f64)>,
+    pub best: Option<(usize, f64)>,
+    pub after: f64, // After the move that was actually played, or the pass
+}
+
+impl TurnReview {
+    // Win probability the roll itself gave or took away, assuming best play
+    pub fn luck(&self) -> f64 {
+        self.best.map_or(self.after, |(_, best)| best) - self.before_roll
+    }
+
+    // Win probability lost by not playing the best move
+    pub fn loss(&self) -> f64 {
+        match (self.best, self.chosen) {
+            (Some((_, best)), Some((_, chosen))) => best - chosen,
+            _ => 0.0,
+        }
+    }
+
+    pub fn is_blunder(&self) -> bool {
+        self.loss() >= BLUNDER_THRESHOLD
+    }
+}
+
+// Totals for one player over the whole game
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PlayerSummary {
+    pub luck: f64,
+    pub decisions: f64, // Never positive: the best you can do is lose nothing
+    pub blunders: usize,
+}
+
+pub struct AnalysisReport {
+    pub turns: Vec<TurnReview>,
+    pub players: Vec<PlayerSummary>,
+}
+
+// Replay the recorded game and compare every decision with the best move
+// by rollout win probability. Turns are spread over worker threads; each
+// turn uses its own seeded dice so the report is reproducible.
+pub fn analyse(history: &[TurnRecord], player_count: usize, rollouts: usize) -> AnalysisReport {
+    let threads = default_threads().max(1);
+    let chunk_size = history.len().div_ceil(threads).max(1);
+
+    let turns: Vec<TurnReview> = thread::scope(|scope| {
+        let workers: Vec<_> = history
+            .chunks(chunk_size)
+            .enumerate()
+            .map(|(chunk_idx, chunk)| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .enumerate()
+                        .map(|(i, record)| review_turn(record, chunk_idx * chunk_size + i + 1, rollouts))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect()
+    });
+
+    let mut players = vec![PlayerSummary::default(); player_count];
+    for review in &turns {
+        let summary = &mut players[review.player_id];
+        summary.luck += review.luck();
+        summary.decisions -= review.loss();
+        if review.is_blunder() {
+            summary.blunders += 1;
+        }
+    }
+
+    AnalysisReport { turns, players }
+}
+
+fn review_turn(record: &TurnRecord, turn: usize, rollouts: usize) -> TurnReview {
+    let mover = record.player_id;
+    let board = &record.board_before;
+    // Every estimate in a turn sees the same dice, so differences between
+    // moves and rolls are not just noise. That also makes estimates of the
+    // same position identical, so each is only played out once; several
+    // rolls often lead to the same position, most of all when they pass.
+    let mut known: HashMap<(u64, PlayerId), f64> = HashMap::new();
+    let mut estimate = |board: &Board, to_move: PlayerId| {
+        *known.entry((board.hash(), to_move)).or_insert_with(|| {
+            let mut rng = StdRng::seed_from_u64(turn as u64);
+            rollout::win_probabilities(board, to_move, &[], rollouts, &mut rng)[mover]
+        })
+    };
+
+    // Value of every legal move for a roll; a pass if there are none
+    let mut move_values = |dice_value: u8| -> Vec<(Option<usize>, f64)> {
+        let legal_moves = board.legal_moves(mover, dice_value as usize);
+        if legal_moves.is_empty() {
+            return vec![(None, estimate(board, (mover + 1) % board.player_count()))];
+        }
+
+        legal_moves
+            .iter()
+            .map(|&piece_idx| {
+                let mut after = board.clone();
+                let result = after.move_piece(mover, piece_idx, dice_value as usize);
+                let value = if result == MoveResult::Finished && after.has_won(mover) {
+                    1.0
+                } else {
+                    estimate(&after, rollout::next_to_move(&after, mover, &result, dice_value, &[]))
+                };
+                (Some(piece_idx), value)
+            })
+            .collect()
+    };
+    let best_of = |values: &[(Option<usize>, f64)]| values.iter().copied().max_by(|a, b| a.1.total_cmp(&b.1)).unwrap();
+
+    // Luck is measured against the average over all six rolls, with best
+    // play after each, rather than against a plain estimate before the roll.
+    // Both sides of the comparison then share the same estimation bias.
+    let mut before_roll = 0.0;
+    let mut actual = Vec::new();
+    for dice_value in 1..=6u8 {
+        let values = move_values(dice_value);
+        before_roll += best_of(&values).1 / 6.0;
+        if dice_value == record.dice_value {
+            actual = values;
+        }
+    }
+
+    let (best_piece, best_value) = best_of(&actual);
+    let (chosen_piece, chosen_value) = actual
+        .iter()
+        .copied()
+        .find(|&(piece_idx, _)| piece_idx == record.chosen)
+        .unwrap_or((best_piece, best_value));
+
+    TurnReview {
+        turn,
+        player_id: mover,
+        dice_value: record.dice_value,
+        before_roll,
+        chosen: chosen_piece.map(|piece_idx| (piece_idx, chosen_value)),
+        best: best_piece.map(|piece_idx| (piece_idx, best_value)),
+        after: chosen_value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn review(before_roll: f64, chosen: Option<f64>, best: Option<f64>, after: f64) -> TurnReview {
+        TurnReview {
+            turn: 1,
+            player_id: 0,
+            dice_value: 3,
+            before_roll,
+            chosen: chosen.map(|value| (0, value)),
+            best: best.map(|value| (1, value)),
+            after,
+        }
+    }
+
+    #[test]
+    fn luck_loss_and_blunders() {
+        let blunder = review(0.4, Some(0.42), Some(0.5), 0.42);
+        assert!((blunder.luck() - 0.1).abs() < 1e-9);
+        assert!((blunder.loss() - 0.08).abs() < 1e-9);
+        assert!(blunder.is_blunder());
+
+        let slip = review(0.4, Some(0.43), Some(0.5), 0.43);
+        assert!((slip.loss() - 0.07).abs() < 1e-9);
+        assert!(!slip.is_blunder());
+
+        // A pass: the roll is all there is to it
+        let pass = review(0.4, None, None, 0.3);
+        assert!((pass.luck() + 0.1).abs() < 1e-9);
+        assert_eq!(pass.loss(), 0.0);
+        assert!(!pass.is_blunder());
+    }
+
+    // Red can send Blue's last piece back to the yard just before it gets
+    // home, or move along the home track instead
+    #[test]
+    fn analyse_reviews_a_fixed_history() {
+        let capture = Board::from_notation("t16h0ff/t20fff 0 - classic").unwrap();
+        let stuck = Board::from_notation("yyyy/t30yyy 0 - classic").unwrap();
+        let turn = |board: &Board, dice_value, chosen| TurnRecord { player_id: 0, dice_value, board_before: board.clone(), chosen };
+        let history = [turn(&capture, 4, Some(1)), turn(&capture, 4, Some(0)), turn(&stuck, 3, None)];
+        let report = analyse(&history, 2, 200);
+        assert_eq!(report.turns.len(), 3);
+        assert_eq!(report.turns.iter().map(|review| review.turn).collect::<Vec<_>>(), [1, 2, 3]);
+
+        let missed = &report.turns[0];
+        assert_eq!(missed.best.map(|(piece_idx, _)| piece_idx), Some(0));
+        assert_eq!(missed.chosen.map(|(piece_idx, _)| piece_idx), Some(1));
+        assert!(missed.is_blunder(), "lost only {}", missed.loss());
+
+        let found = &report.turns[1];
+        assert_eq!(found.chosen, found.best);
+        assert_eq!(found.loss(), 0.0);
+
+        // Only a six gets a piece out, so a 3 is a forced pass and bad luck
+        let forced = &report.turns[2];
+        assert_eq!((forced.chosen, forced.best), (None, None));
+        assert_eq!(forced.loss(), 0.0);
+        assert!(forced.luck() < 0.0, "{}", forced.luck());
+
+        let red = report.players[0];
+        let luck: f64 = report.turns.iter().map(TurnReview::luck).sum();
+        assert!((red.luck - luck).abs() < 1e-9);
+        assert!((red.decisions + missed.loss()).abs() < 1e-9);
+        assert_eq!(red.blunders, 1);
+        let blue = report.players[1];
+        assert_eq!((blue.luck, blue.decisions, blue.blunders), (0.0, 0.0, 0));
+    }
+}
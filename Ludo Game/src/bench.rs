@@ -0,0 +1,109 @@
+use crate::board::{Board, MoveResult, TOTAL_PIECES_PER_PLAYER};
+use crate::cli::Options;
+use crate::rollout;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::hint::black_box;
+use std::time::Instant;
+
+// `ludo bench`: measure how fast the board can be cloned and how fast moves
+// can be generated and played, over positions taken from random games
+pub fn run(options: &Options) -> Result<(), String> {
+    let players = options.get_or("players", 4usize)?;
+    let positions = options.get_or("positions", 1000usize)?;
+    let iterations = options.get_or("iterations", 1_000_000usize)?;
+    let seed = options.get_or("seed", 1u64)?;
+
+    if !(2..=4).contains(&players) {
+        return Err("--players must be between 2 and 4".to_string());
+    }
+    if positions == 0 || iterations == 0 {
+        return Err("--positions and --iterations must be at least 1".to_string());
+    }
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let samples = sample_positions(players, positions, &mut rng);
+    let rolls: Vec<u8> = (0..samples.len()).map(|_| rng.gen_range(1..=6)).collect();
+    println!("{} positions from random {}-player games, {} iterations each\n", samples.len(), players, iterations);
+
+    measure("Board clone", iterations, |i| {
+        black_box(samples[i % samples.len()].clone());
+    });
+    measure("Move generation (all six rolls)", iterations, |i| {
+        let (board, to_move) = &samples[i % samples.len()];
+        for steps in 1..=6 {
+            black_box(board.legal_moves(*to_move, steps));
+        }
+    });
+    measure("Clone and play a move", iterations, |i| {
+        let (board, to_move) = &samples[i % samples.len()];
+        let steps = rolls[i % rolls.len()] as usize;
+        if let Some(&piece_idx) = board.legal_moves(*to_move, steps).first() {
+            let mut after = board.clone();
+            black_box(after.move_piece(*to_move, piece_idx, steps));
+        }
+    });
+    measure("Piece lookup (every piece)", iterations, |i| {
+        let (board, _) = &samples[i % samples.len()];
+        for player_id in 0..players {
+            for piece_idx in 0..TOTAL_PIECES_PER_PLAYER {
+                black_box(board.get_piece_location(player_id, piece_idx));
+            }
+        }
+    });
+
+    Ok(())
+}
+
+// Time `iterations` calls and print the throughput
+fn measure(label: &str, iterations: usize, mut operation: impl FnMut(usize)) {
+    let started = Instant::now();
+    for i in 0..iterations {
+        operation(i);
+    }
+    let elapsed = started.elapsed().as_secs_f64();
+
+    println!(
+        "{:<34} {:>8.2} M/s  ({:.0} ns each)",
+        label,
+        iterations as f64 / elapsed / 1e6,
+        elapsed * 1e9 / iterations as f64
+    );
+}
+
+// Positions spread over whole games, so the sample covers openings,
+// crowded middle games and endgames alike
+fn sample_positions(players: usize, count: usize, rng: &mut StdRng) -> Vec<(Board, usize)> {
+    let mut samples = Vec::with_capacity(count);
+
+    while samples.len() < count {
+        let mut board = Board::new(players);
+        let mut to_move = 0;
+
+        loop {
+            samples.push((board.clone(), to_move));
+            if samples.len() == count {
+                break;
+            }
+
+            let dice_value = rng.gen_range(1..=6u8);
+            let legal_moves = board.legal_moves(to_move, dice_value as usize);
+            let piece_idx = match legal_moves.choose(rng) {
+                Some(&piece_idx) => piece_idx,
+                None => {
+                    to_move = (to_move + 1) % players;
+                    continue;
+                }
+            };
+
+            let result = board.move_piece(to_move, piece_idx, dice_value as usize);
+            if result == MoveResult::Finished && board.has_won(to_move) {
+                break;
+            }
+            to_move = rollout::next_to_move(&board, to_move, &result, dice_value, &[]);
+        }
+    }
+
+    samples
+}
@@ -1,103 +1,297 @@
+use crate::dice::derive_seed;
 use crate::player::{Piece, PlayerId};
-use colored::{ColoredString, Colorize};
+use colored::Colorize;
 use std::collections::HashMap;
 
 // Constants for the board
 pub const HOME_SPACES: usize = 6;
 pub const MAIN_TRACK_SPACES: usize = 52;
 pub const TOTAL_PIECES_PER_PLAYER: usize = 4;
+pub const MAX_PLAYERS: usize = 4;
+// Steps from the yard to the finish: onto the start square, round the
+// main track, up the home track and off the end
+pub const PATH_LENGTH: usize = MAIN_TRACK_SPACES + 2;
+
+// Name of the rules implemented here, for reports and saved positions
+pub const RULESET_ID: &str = "classic";
 
 // Board positions type aliases
 pub type Position = usize;
 pub type HomePosition = usize;
 
-#[derive(Debug)]
+// Pieces are stored as their progress along their own path (see `progress`):
+// 0 in the yard, 1..HOME_START on the main track, HOME_START..FINISHED on
+// the home track
+const YARD: u8 = 0;
+const HOME_START: usize = MAIN_TRACK_SPACES - HOME_SPACES + 2;
+const FINISHED: u8 = PATH_LENGTH as u8;
+// Furthest a piece can be from its start square and still be on the main track
+const HOME_ENTRY_DISTANCE: usize = MAIN_TRACK_SPACES - HOME_SPACES;
+
+// Random keys for Zobrist hashing: one per piece and progress value, one
+// per player to move and one for a pending bonus turn. They are generated
+// at compile time from a fixed seed, so hashes are stable between runs.
+const ZOBRIST_SEED: u64 = 0x4C55_444F_5A4F_4252;
+static PIECE_KEYS: [[[u64; PATH_LENGTH + 1]; TOTAL_PIECES_PER_PLAYER]; MAX_PLAYERS] = piece_keys();
+static TO_MOVE_KEYS: [u64; MAX_PLAYERS] = [
+    derive_seed(ZOBRIST_SEED, 1_000),
+    derive_seed(ZOBRIST_SEED, 1_001),
+    derive_seed(ZOBRIST_SEED, 1_002),
+    derive_seed(ZOBRIST_SEED, 1_003),
+];
+const BONUS_TURN_KEY: u64 = derive_seed(ZOBRIST_SEED, 2_000);
+
+const fn piece_keys() -> [[[u64; PATH_LENGTH + 1]; TOTAL_PIECES_PER_PLAYER]; MAX_PLAYERS] {
+    let mut keys = [[[0; PATH_LENGTH + 1]; TOTAL_PIECES_PER_PLAYER]; MAX_PLAYERS];
+    let mut index = 0;
+    while index < MAX_PLAYERS * TOTAL_PIECES_PER_PLAYER * (PATH_LENGTH + 1) {
+        let progress = index % (PATH_LENGTH + 1);
+        let piece_idx = index / (PATH_LENGTH + 1) % TOTAL_PIECES_PER_PLAYER;
+        let player_id = index / (PATH_LENGTH + 1) / TOTAL_PIECES_PER_PLAYER;
+        keys[player_id][piece_idx][progress] = derive_seed(ZOBRIST_SEED, index as u64);
+        index += 1;
+    }
+    keys
+}
+
+#[derive(Debug, Clone)]
 pub struct Board {
-    // Main track - shared by all players
-    main_track: Vec<Vec<(PlayerId, usize)>>, // Player ID and piece index
+    // Progress of every piece, by player and piece index. What stands on
+    // each square is derived from this rather than stored separately.
+    pieces: [[u8; TOTAL_PIECES_PER_PLAYER]; MAX_PLAYERS],
     
-    // Home tracks - one per player
-    home_tracks: HashMap<PlayerId, Vec<Option<usize>>>, // Maps player ID to their home track pieces
+    player_count: usize,
     
-    // Yard positions - where pieces start and return when captured
-    yards: HashMap<PlayerId, Vec<bool>>, // true if piece is in yard
+    // Whose turn it is, and whether it is an extra turn earned by a six or
+    // a capture. The board does not advance these itself; whoever runs the
+    // game keeps them up to date with `set_turn`.
+    to_move: PlayerId,
+    bonus_turn: bool,
     
-    // Finished positions - destination for pieces
-    finished: HashMap<PlayerId, Vec<bool>>, // true if piece is finished
-    
-    // Starting positions for each player on the main track
-    player_starts: HashMap<PlayerId, Position>,
+    // Zobrist hash of all of the above, updated with every change
+    hash: u64,
 }
 
 impl Board {
     pub fn new(player_count: usize) -> Self {
-        let mut main_track = vec![Vec::new(); MAIN_TRACK_SPACES];
-        let mut home_tracks = HashMap::new();
-        let mut yards = HashMap::new();
-        let mut finished = HashMap::new();
-        let mut player_starts = HashMap::new();
-        
-        // Set up player-specific data
-        for player_id in 0..player_count {
-            home_tracks.insert(player_id, vec![None; HOME_SPACES]);
-            yards.insert(player_id, vec![true; TOTAL_PIECES_PER_PLAYER]); // All pieces start in yard
-            finished.insert(player_id, vec![false; TOTAL_PIECES_PER_PLAYER]);
-            
-            // Starting positions are evenly distributed around the board
-            let start_pos = (player_id * (MAIN_TRACK_SPACES / player_count)) % MAIN_TRACK_SPACES;
-            player_starts.insert(player_id, start_pos);
+        assert!(
+            (1..=MAX_PLAYERS).contains(&player_count),
+            "a board seats 1 to {} players, not {}",
+            MAX_PLAYERS,
+            player_count
+        );
+        
+        // All pieces start in the yard
+        let mut board = Board {
+            pieces: [[YARD; TOTAL_PIECES_PER_PLAYER]; MAX_PLAYERS],
+            player_count,
+            to_move: 0,
+            bonus_turn: false,
+            hash: 0,
+        };
+        board.hash = board.compute_hash();
+        board
+    }
+    
+    // Number of players the board was set up for
+    pub fn player_count(&self) -> usize {
+        self.player_count
+    }
+    
+    pub fn to_move(&self) -> PlayerId {
+        self.to_move
+    }
+    
+    pub fn bonus_turn(&self) -> bool {
+        self.bonus_turn
+    }
+    
+    // Record whose turn it is and whether it is an extra turn
+    pub fn set_turn(&mut self, player_id: PlayerId, bonus_turn: bool) {
+        self.hash ^= TO_MOVE_KEYS[self.to_move] ^ TO_MOVE_KEYS[player_id];
+        if self.bonus_turn != bonus_turn {
+            self.hash ^= BONUS_TURN_KEY;
+        }
+        self.to_move = player_id;
+        self.bonus_turn = bonus_turn;
+    }
+    
+    // 64-bit Zobrist hash of the piece placement, the player to move and
+    // any pending bonus turn. Equal positions always hash equally.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+    
+    // The hash worked out from nothing; `hash` keeps the same value
+    // up to date incrementally
+    fn compute_hash(&self) -> u64 {
+        let mut hash = TO_MOVE_KEYS[self.to_move];
+        if self.bonus_turn {
+            hash ^= BONUS_TURN_KEY;
+        }
+        for player_id in 0..self.player_count {
+            for piece_idx in 0..TOTAL_PIECES_PER_PLAYER {
+                hash ^= PIECE_KEYS[player_id][piece_idx][self.pieces[player_id][piece_idx] as usize];
+            }
+        }
+        hash
+    }
+    
+    // The whole position on one line, for bug reports, puzzles and test
+    // fixtures: each player's pieces in index order separated by '/', the
+    // player to move, 'b' for a bonus turn or '-', and the ruleset. Pieces
+    // are `y` (yard), `t<square>` (main track), `h<square>` (home track)
+    // and `f` (finished), e.g. `t12yh2f/t14t14yy 1 - classic`.
+    pub fn to_notation(&self) -> String {
+        let placement: Vec<String> = (0..self.player_count)
+            .map(|player_id| {
+                (0..TOTAL_PIECES_PER_PLAYER)
+                    .map(|piece_idx| match self.get_piece_location(player_id, piece_idx) {
+                        PieceLocation::Yard => "y".to_string(),
+                        PieceLocation::MainTrack(pos) => format!("t{}", pos),
+                        PieceLocation::HomeTrack(home_pos) => format!("h{}", home_pos),
+                        PieceLocation::Finished => "f".to_string(),
+                    })
+                    .collect()
+            })
+            .collect();
+        
+        format!(
+            "{} {} {} {}",
+            placement.join("/"),
+            self.to_move,
+            if self.bonus_turn { "b" } else { "-" },
+            RULESET_ID
+        )
+    }
+    
+    // The reverse of `to_notation`. Positions for other rulesets and pieces
+    // where the rules never put them are rejected.
+    pub fn from_notation(text: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = text.split_whitespace().collect();
+        let [placement, to_move, bonus_turn, ruleset] = fields[..] else {
+            return Err(format!(
+                "notation '{}' needs four fields: pieces, player to move, bonus turn and ruleset",
+                text
+            ));
+        };
+        if ruleset != RULESET_ID {
+            return Err(format!("position is for the '{}' rules, but this build plays '{}'", ruleset, RULESET_ID));
         }
         
-        Board {
-            main_track,
-            home_tracks,
-            yards,
-            finished,
-            player_starts,
+        let players: Vec<&str> = placement.split('/').collect();
+        // Boards for one player exist for tests, but no game is played on them
+        if !(2..=MAX_PLAYERS).contains(&players.len()) {
+            return Err(format!("a game needs 2 to {} players, not {}", MAX_PLAYERS, players.len()));
         }
+        let mut board = Board::new(players.len());
+        for (player_id, pieces) in players.iter().enumerate() {
+            let locations = parse_pieces(pieces)?;
+            if locations.len() != TOTAL_PIECES_PER_PLAYER {
+                return Err(format!("player {} needs {} pieces, not '{}'", player_id, TOTAL_PIECES_PER_PLAYER, pieces));
+            }
+            for (piece_idx, location) in locations.into_iter().enumerate() {
+                board.set_piece_location(player_id, piece_idx, location)?;
+            }
+        }
+        
+        let to_move = match to_move.parse::<PlayerId>() {
+            Ok(player_id) if player_id < board.player_count => player_id,
+            _ => return Err(format!("invalid player to move '{}'", to_move)),
+        };
+        let bonus_turn = match bonus_turn {
+            "b" => true,
+            "-" => false,
+            _ => return Err(format!("bonus turn must be 'b' or '-', not '{}'", bonus_turn)),
+        };
+        board.set_turn(to_move, bonus_turn);
+        
+        Ok(board)
+    }
+    
+    // Put a piece at a new progress value, keeping the hash in step
+    fn place(&mut self, player_id: PlayerId, piece_idx: usize, progress: u8) {
+        let keys = &PIECE_KEYS[player_id][piece_idx];
+        self.hash ^= keys[self.pieces[player_id][piece_idx] as usize] ^ keys[progress as usize];
+        self.pieces[player_id][piece_idx] = progress;
+    }
+    
+    // Starting positions are evenly distributed around the board
+    fn start_position(&self, player_id: PlayerId) -> Position {
+        (player_id * (MAIN_TRACK_SPACES / self.player_count)) % MAIN_TRACK_SPACES
+    }
+    
+    // The progress a player's piece has when standing on a main track
+    // square, or None if the player's pieces never stand there (the squares
+    // just before their start, which they skip to enter the home track)
+    fn progress_on_square(&self, player_id: PlayerId, pos: Position) -> Option<u8> {
+        let distance = (MAIN_TRACK_SPACES + pos - self.start_position(player_id)) % MAIN_TRACK_SPACES;
+        if distance <= HOME_ENTRY_DISTANCE {
+            Some(distance as u8 + 1)
+        } else {
+            None
+        }
+    }
+    
+    fn location_for(&self, player_id: PlayerId, progress: usize) -> PieceLocation {
+        match progress {
+            0 => PieceLocation::Yard,
+            p if p < HOME_START => PieceLocation::MainTrack((self.start_position(player_id) + p - 1) % MAIN_TRACK_SPACES),
+            p if p < FINISHED as usize => PieceLocation::HomeTrack(p - HOME_START),
+            _ => PieceLocation::Finished,
+        }
+    }
+    
+    // Pieces currently standing on a main track square, by player and then
+    // piece index
+    pub fn pieces_at(&self, pos: Position) -> impl Iterator<Item = Piece> + '_ {
+        (0..self.player_count).flat_map(move |player_id| {
+            let progress = self.progress_on_square(player_id, pos);
+            (0..TOTAL_PIECES_PER_PLAYER)
+                .filter(move |&piece_idx| Some(self.pieces[player_id][piece_idx]) == progress)
+                .map(move |piece_idx| Piece::new(player_id, piece_idx))
+        })
     }
     
     // Check if a piece is in the yard
     pub fn is_in_yard(&self, player_id: PlayerId, piece_idx: usize) -> bool {
-        self.yards[&player_id][piece_idx]
+        self.pieces[player_id][piece_idx] == YARD
     }
     
     // Check if a piece has finished
     pub fn is_finished(&self, player_id: PlayerId, piece_idx: usize) -> bool {
-        self.finished[&player_id][piece_idx]
+        self.pieces[player_id][piece_idx] == FINISHED
     }
     
     // Get piece's location type
     pub fn get_piece_location(&self, player_id: PlayerId, piece_idx: usize) -> PieceLocation {
-        if self.is_in_yard(player_id, piece_idx) {
-            return PieceLocation::Yard;
-        }
-        
-        if self.is_finished(player_id, piece_idx) {
-            return PieceLocation::Finished;
-        }
-        
-        // Check main track
-        for (pos, pieces) in self.main_track.iter().enumerate() {
-            for &(pid, idx) in pieces {
-                if pid == player_id && idx == piece_idx {
-                    return PieceLocation::MainTrack(pos);
-                }
-            }
-        }
-        
-        // Check home track
-        let home_track = &self.home_tracks[&player_id];
-        for (pos, &piece_opt) in home_track.iter().enumerate() {
-            if let Some(idx) = piece_opt {
-                if idx == piece_idx {
-                    return PieceLocation::HomeTrack(pos);
+        self.location_for(player_id, self.pieces[player_id][piece_idx] as usize)
+    }
+    
+    // Put a piece straight onto a location, for setting up positions.
+    // Fails where the player's pieces can never stand or a home track
+    // square is already taken.
+    pub fn set_piece_location(&mut self, player_id: PlayerId, piece_idx: usize, location: PieceLocation) -> Result<(), String> {
+        let progress = match location {
+            PieceLocation::Yard => YARD,
+            PieceLocation::MainTrack(pos) if pos < MAIN_TRACK_SPACES => self
+                .progress_on_square(player_id, pos)
+                .ok_or_else(|| format!("player {}'s pieces never stand on square {}", player_id, pos))?,
+            PieceLocation::HomeTrack(home_pos) if home_pos < HOME_SPACES => {
+                let progress = (HOME_START + home_pos) as u8;
+                let taken = (0..TOTAL_PIECES_PER_PLAYER)
+                    .any(|other_idx| other_idx != piece_idx && self.pieces[player_id][other_idx] == progress);
+                if taken {
+                    return Err(format!("player {} already has a piece on home square {}", player_id, home_pos));
                 }
-            }
-        }
+                progress
+            },
+            PieceLocation::Finished => FINISHED,
+            _ => return Err(format!("{:?} is not on the board", location)),
+        };
         
-        // This should never happen if the board state is valid
-        panic!("Piece not found on board: Player {}, Piece {}", player_id, piece_idx);
+        self.place(player_id, piece_idx, progress);
+        Ok(())
     }
     
     // Move a piece from yard to start position
@@ -106,125 +300,177 @@ impl Board {
             return false;
         }
         
-        let start_pos = self.player_starts[&player_id];
+        self.place(player_id, piece_idx, 1);
+        true
+    }
+    
+    // Move a piece by a roll. Landing on a main track square sends every
+    // opponent piece there back to its yard; coming out of the yard does not.
+    pub fn move_piece(&mut self, player_id: PlayerId, piece_idx: usize, steps: usize) -> MoveResult {
+        let target = match self.target_progress(player_id, piece_idx, steps) {
+            Some(target) => target,
+            None => return MoveResult::InvalidMove,
+        };
         
-        // Update yard status
-        self.yards.get_mut(&player_id).unwrap()[piece_idx] = false;
+        if self.is_in_yard(player_id, piece_idx) {
+            self.move_from_yard_to_start(player_id, piece_idx);
+            return MoveResult::Moved;
+        }
         
-        // Add to main track
-        self.main_track[start_pos].push((player_id, piece_idx));
+        let victims = self.captures(player_id, piece_idx, steps);
+        self.place(player_id, piece_idx, target);
+        for victim in &victims {
+            self.place(victim.player_id, victim.index, YARD);
+        }
         
-        true
+        match self.location_for(player_id, target as usize) {
+            PieceLocation::Finished => MoveResult::Finished,
+            _ if !victims.is_empty() => MoveResult::Captured,
+            _ => MoveResult::Moved,
+        }
     }
     
-    // Move a piece on the main track
-    pub fn move_piece(&mut self, player_id: PlayerId, piece_idx: usize, steps: usize) -> MoveResult {
-        match self.get_piece_location(player_id, piece_idx) {
-            PieceLocation::Yard => {
-                // Can only move out of yard with a 6
-                if steps == 6 {
-                    self.move_from_yard_to_start(player_id, piece_idx);
-                    MoveResult::Moved
+    // The opponent pieces a move would send back to their yards: all of
+    // them on the main track square it lands on. Leaving the yard never
+    // captures, even onto an occupied start square.
+    pub fn captures(&self, player_id: PlayerId, piece_idx: usize, steps: usize) -> Vec<Piece> {
+        if self.is_in_yard(player_id, piece_idx) {
+            return Vec::new();
+        }
+        match self.destination(player_id, piece_idx, steps) {
+            Some(PieceLocation::MainTrack(pos)) => self.pieces_at(pos).filter(|piece| piece.player_id != player_id).collect(),
+            _ => Vec::new(),
+        }
+    }
+    
+    // Progress a piece would have after a roll, or None if it cannot move
+    fn target_progress(&self, player_id: PlayerId, piece_idx: usize, steps: usize) -> Option<u8> {
+        let progress = self.pieces[player_id][piece_idx] as usize;
+        
+        let target = match progress {
+            // Can only move out of yard with a 6
+            0 => return if steps == 6 { Some(1) } else { None },
+            p if p < HOME_START => {
+                // A piece that would pass the end of the main track without
+                // reaching the home track wraps round to its start square
+                let distance_from_start = (p - 1 + steps) % MAIN_TRACK_SPACES;
+                if distance_from_start > HOME_ENTRY_DISTANCE {
+                    HOME_START + distance_from_start - HOME_ENTRY_DISTANCE - 1
                 } else {
-                    MoveResult::InvalidMove
+                    // Main track squares can hold any number of pieces
+                    return Some(distance_from_start as u8 + 1);
                 }
             },
-            PieceLocation::MainTrack(curr_pos) => {
-                // Remove from current position
-                let position = &mut self.main_track[curr_pos];
-                let index = position.iter().position(|&(pid, idx)| pid == player_id && idx == piece_idx).unwrap();
-                position.remove(index);
-                
-                // Calculate new position
-                let start_pos = self.player_starts[&player_id];
-                let absolute_pos = (curr_pos + steps) % MAIN_TRACK_SPACES;
-                
-                // Check if we've gone around the board and need to enter home track
-                let distance_from_start = (MAIN_TRACK_SPACES + absolute_pos - start_pos) % MAIN_TRACK_SPACES;
-                let home_entry_distance = MAIN_TRACK_SPACES - HOME_SPACES;
-                
-                if distance_from_start > home_entry_distance {
-                    // Enter home track
-                    let home_pos = distance_from_start - home_entry_distance - 1;
-                    
-                    if home_pos < HOME_SPACES {
-                        // Valid home track position
-                        if self.home_tracks[&player_id][home_pos].is_some() {
-                            // Space already occupied by own piece
-                            return MoveResult::InvalidMove;
-                        }
-                        
-                        self.home_tracks.get_mut(&player_id).unwrap()[home_pos] = Some(piece_idx);
-                        return MoveResult::Moved;
-                    } else {
-                        // Overshooting home track
-                        return MoveResult::InvalidMove;
-                    }
-                }
-                
-                // Check for captures
-                let mut captured = false;
-                if !self.main_track[absolute_pos].is_empty() {
-                    let mut i = 0;
-                    while i < self.main_track[absolute_pos].len() {
-                        let (other_pid, other_idx) = self.main_track[absolute_pos][i];
-                        
-                        if other_pid != player_id {
-                            // Capture other player's piece
-                            self.main_track[absolute_pos].remove(i);
-                            self.yards.get_mut(&other_pid).unwrap()[other_idx] = true;
-                            captured = true;
-                        } else {
-                            i += 1;
-                        }
-                    }
-                }
-                
-                // Add to new position
-                self.main_track[absolute_pos].push((player_id, piece_idx));
-                
-                if captured {
-                    MoveResult::Captured
-                } else {
-                    MoveResult::Moved
+            p if p < FINISHED as usize => p + steps,
+            _ => return None,
+        };
+        
+        // The finish must be reached exactly, and home track squares hold
+        // one piece each
+        if target > FINISHED as usize {
+            return None;
+        }
+        let target = target as u8;
+        if target != FINISHED && self.pieces[player_id].contains(&target) {
+            return None;
+        }
+        Some(target)
+    }
+    
+    // Work out where a piece would end up without moving it.
+    // Returns None when `move_piece` would reject the move.
+    pub fn destination(&self, player_id: PlayerId, piece_idx: usize, steps: usize) -> Option<PieceLocation> {
+        self.target_progress(player_id, piece_idx, steps)
+            .map(|target| self.location_for(player_id, target as usize))
+    }
+    
+    // Pieces the player can legally move with the given roll
+    pub fn legal_moves(&self, player_id: PlayerId, steps: usize) -> Vec<usize> {
+        (0..TOTAL_PIECES_PER_PLAYER)
+            .filter(|&piece_idx| self.target_progress(player_id, piece_idx, steps).is_some())
+            .collect()
+    }
+    
+    // How far a piece has come: 0 in the yard, 1 on its start square,
+    // up to PATH_LENGTH once it has finished
+    pub fn progress(&self, player_id: PlayerId, piece_idx: usize) -> usize {
+        self.pieces[player_id][piece_idx] as usize
+    }
+    
+    // Opponent pieces that could land on `pos` with a single roll, and so
+    // capture whatever `victim` has standing there
+    pub fn attackers(&self, victim: PlayerId, pos: Position) -> Vec<Attacker> {
+        let mut found = Vec::new();
+        
+        for distance in 1..=6 {
+            let from = (pos + MAIN_TRACK_SPACES - distance) % MAIN_TRACK_SPACES;
+            for piece in self.pieces_at(from) {
+                if piece.player_id != victim
+                    && self.destination(piece.player_id, piece.index, distance) == Some(PieceLocation::MainTrack(pos))
+                {
+                    found.push(Attacker { piece, distance });
                 }
-            },
-            PieceLocation::HomeTrack(home_pos) => {
-                let new_pos = home_pos + steps;
-                
-                if new_pos >= HOME_SPACES {
-                    // Finish the piece if exact
-                    if new_pos == HOME_SPACES {
-                        self.home_tracks.get_mut(&player_id).unwrap()[home_pos] = None;
-                        self.finished.get_mut(&player_id).unwrap()[piece_idx] = true;
-                        MoveResult::Finished
-                    } else {
-                        // Can't overshoot finish
-                        MoveResult::InvalidMove
-                    }
-                } else {
-                    // Move within home track
-                    if self.home_tracks[&player_id][new_pos].is_some() {
-                        // Space already occupied
-                        return MoveResult::InvalidMove;
-                    }
-                    
-                    self.home_tracks.get_mut(&player_id).unwrap()[home_pos] = None;
-                    self.home_tracks.get_mut(&player_id).unwrap()[new_pos] = Some(piece_idx);
-                    MoveResult::Moved
+            }
+        }
+        
+        found
+    }
+    
+    // The player's pieces on the main track that an opponent could capture
+    // before the player's next turn. Each opponent gets one roll and is
+    // assumed to take the capture whenever that roll allows it; bonus turns
+    // and other pieces moving in between are not considered.
+    pub fn threats(&self, player_id: PlayerId) -> Vec<Threat> {
+        let mut threats = Vec::new();
+        
+        for piece_idx in 0..TOTAL_PIECES_PER_PLAYER {
+            let pos = match self.get_piece_location(player_id, piece_idx) {
+                PieceLocation::MainTrack(pos) => pos,
+                _ => continue,
+            };
+            
+            let attackers = self.attackers(player_id, pos);
+            if attackers.is_empty() {
+                continue;
+            }
+            
+            let mut escape_probability = 1.0;
+            for opponent in (0..self.player_count()).filter(|&pid| pid != player_id) {
+                let mut hitting_rolls = [false; 6];
+                for attacker in attackers.iter().filter(|attacker| attacker.piece.player_id == opponent) {
+                    hitting_rolls[attacker.distance - 1] = true;
                 }
-            },
-            PieceLocation::Finished => MoveResult::InvalidMove,
+                let hits = hitting_rolls.iter().filter(|&&hit| hit).count();
+                escape_probability *= 1.0 - hits as f64 / 6.0;
+            }
+            
+            threats.push(Threat {
+                piece: Piece::new(player_id, piece_idx),
+                position: pos,
+                attackers,
+                capture_probability: 1.0 - escape_probability,
+            });
         }
+        
+        threats
     }
     
     // Check if a player has won
     pub fn has_won(&self, player_id: PlayerId) -> bool {
-        self.finished[&player_id].iter().all(|&finished| finished)
+        self.pieces[player_id].iter().all(|&progress| progress == FINISHED)
     }
     
-    // Render the board as a string
-    pub fn render(&self, player_colors: &HashMap<PlayerId, colored::Color>) -> String {
+    // Render the board as a string. The viewer's pieces that are in danger
+    // are marked with a `!` and listed below the track, and estimated win
+    // probabilities, if given, are shown next to each player.
+    pub fn render(
+        &self,
+        player_colors: &HashMap<PlayerId, colored::Color>,
+        viewer: PlayerId,
+        win_probabilities: Option<&[f64]>,
+    ) -> String {
+        let threats = self.threats(viewer);
+        
         let mut output = String::new();
         
         // Display board header
@@ -233,12 +479,19 @@ impl Board {
         // Render main track
         output.push_str("Main Track:\n");
         for i in 0..MAIN_TRACK_SPACES {
-            let pos_str = if self.main_track[i].is_empty() {
-                format!("{:2}", i).normal()
-            } else {
-                let (player_id, piece_idx) = self.main_track[i][0];
-                let color = player_colors[&player_id];
-                format!("P{}{}", player_id, piece_idx).color(color)
+            // A piece just out of its yard shares the square with whatever
+            // stood there, so the viewer's threatened piece is shown first
+            let threatened = threats.iter().find(|threat| threat.position == i).map(|threat| threat.piece);
+            let pos_str = match threatened.or_else(|| self.pieces_at(i).next()) {
+                Some(piece) => {
+                    let label = format!("P{}{}", piece.player_id, piece.index).color(player_colors[&piece.player_id]);
+                    if threatened.is_some() {
+                        format!("{}!", label).bold()
+                    } else {
+                        label
+                    }
+                }
+                None => format!("{:2}", i).normal(),
             };
             
             output.push_str(&format!("[{}]", pos_str));
@@ -248,39 +501,56 @@ impl Board {
             }
         }
         
+        // Render the viewer's pieces in danger
+        if !threats.is_empty() {
+            output.push_str("\nIn danger:\n");
+            for threat in &threats {
+                let attackers: Vec<String> = threat
+                    .attackers
+                    .iter()
+                    .map(|attacker| format!("P{}{} at {}", attacker.piece.player_id, attacker.piece.index, attacker.distance))
+                    .collect();
+                output.push_str(&format!(
+                    "  Piece {} on square {}: {:.0}% chance of capture before your next turn ({})\n",
+                    threat.piece.index,
+                    threat.position,
+                    threat.capture_probability * 100.0,
+                    attackers.join(", ")
+                ).color(player_colors[&viewer]).to_string());
+            }
+        }
+        
         // Render player information
         output.push_str("\nPlayers:\n");
         
-        for (&player_id, yard) in &self.yards {
+        for player_id in 0..self.player_count {
             let color = player_colors[&player_id];
             output.push_str(&format!("Player {}: ", player_id).color(color).to_string());
             
+            if let Some(probabilities) = win_probabilities {
+                output.push_str(&format!("({:4.1}% to win) ", probabilities[player_id] * 100.0));
+            }
+            
             // Yard pieces
             output.push_str("Yard: ");
-            for (idx, &in_yard) in yard.iter().enumerate() {
-                if in_yard {
-                    output.push_str(&format!("{} ", idx).color(color).to_string());
-                }
+            for idx in (0..TOTAL_PIECES_PER_PLAYER).filter(|&idx| self.is_in_yard(player_id, idx)) {
+                output.push_str(&format!("{} ", idx).color(color).to_string());
             }
             
             // Home track
             output.push_str("| Home: ");
-            if let Some(home_track) = self.home_tracks.get(&player_id) {
-                for (pos, &piece_opt) in home_track.iter().enumerate() {
-                    if let Some(piece_idx) = piece_opt {
-                        output.push_str(&format!("{}:{} ", pos, piece_idx).color(color).to_string());
-                    }
+            for pos in 0..HOME_SPACES {
+                for piece_idx in (0..TOTAL_PIECES_PER_PLAYER)
+                    .filter(|&idx| self.get_piece_location(player_id, idx) == PieceLocation::HomeTrack(pos))
+                {
+                    output.push_str(&format!("{}:{} ", pos, piece_idx).color(color).to_string());
                 }
             }
             
             // Finished pieces
             output.push_str("| Finished: ");
-            if let Some(finished_pieces) = self.finished.get(&player_id) {
-                for (idx, &is_finished) in finished_pieces.iter().enumerate() {
-                    if is_finished {
-                        output.push_str(&format!("{} ", idx).color(color).to_string());
-                    }
-                }
+            for idx in (0..TOTAL_PIECES_PER_PLAYER).filter(|&idx| self.is_finished(player_id, idx)) {
+                output.push_str(&format!("{} ", idx).color(color).to_string());
             }
             
             output.push('\n');
@@ -290,7 +560,49 @@ impl Board {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+// An opponent piece that could capture on a square with a single roll
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Attacker {
+    pub piece: Piece,
+    pub distance: usize, // Roll it needs
+}
+
+// One of a player's pieces that could be captured
+#[derive(Debug, Clone, PartialEq)]
+pub struct Threat {
+    pub piece: Piece,
+    pub position: Position,
+    pub attackers: Vec<Attacker>,
+    pub capture_probability: f64, // Before the owner's next turn
+}
+
+// One player's pieces from `Board::to_notation`: a letter per piece,
+// followed by the square for pieces on the track
+fn parse_pieces(text: &str) -> Result<Vec<PieceLocation>, String> {
+    let mut locations = Vec::new();
+    let mut rest = text;
+    
+    while let Some(kind) = rest.chars().next() {
+        let after_kind = &rest[kind.len_utf8()..];
+        let digits = after_kind.find(|c: char| !c.is_ascii_digit()).unwrap_or(after_kind.len());
+        let (number, after) = after_kind.split_at(digits);
+        let invalid = || format!("invalid piece '{}' in '{}'", &rest[..rest.len() - after.len()], text);
+        let square = || number.parse::<usize>().map_err(|_| invalid());
+        
+        locations.push(match kind {
+            'y' if number.is_empty() => PieceLocation::Yard,
+            'f' if number.is_empty() => PieceLocation::Finished,
+            't' => PieceLocation::MainTrack(square()?),
+            'h' => PieceLocation::HomeTrack(square()?),
+            _ => return Err(invalid()),
+        });
+        rest = after;
+    }
+    
+    Ok(locations)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PieceLocation {
     Yard,
     MainTrack(Position),
@@ -304,4 +616,194 @@ pub enum MoveResult {
     Captured,
     Finished,
     InvalidMove,
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::{Rng, SeedableRng};
+    
+    // Play random games of two to four players to the end, keeping the
+    // turn state as `Game` does, and show `check` the board after every
+    // move and every change of turn
+    fn play_random_games(seed: u64, games: usize, mut check: impl FnMut(&Board)) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        
+        for game in 0..games {
+            let player_count = 2 + game % 3;
+            let mut board = Board::new(player_count);
+            let mut to_move = 0;
+            
+            while !(0..player_count).any(|player_id| board.has_won(player_id)) {
+                let steps = rng.gen_range(1..=6);
+                let result = match board.legal_moves(to_move, steps).choose(&mut rng) {
+                    Some(&piece_idx) => board.move_piece(to_move, piece_idx, steps),
+                    None => MoveResult::InvalidMove,
+                };
+                check(&board);
+                
+                let bonus_turn = result != MoveResult::InvalidMove && (result == MoveResult::Captured || steps == 6);
+                if !bonus_turn {
+                    to_move = (to_move + 1) % player_count;
+                }
+                board.set_turn(to_move, bonus_turn);
+                check(&board);
+            }
+        }
+    }
+    
+    // Check the incremental hash against a full recomputation
+    #[test]
+    fn incremental_hash_matches_recomputation() {
+        play_random_games(39, 200, |board| assert_eq!(board.hash(), board.compute_hash()));
+    }
+    
+    #[test]
+    fn hash_covers_side_to_move_and_bonus_turn() {
+        let mut board = Board::new(4);
+        let initial = board.hash();
+        
+        board.set_turn(1, false);
+        let second_player = board.hash();
+        board.set_turn(1, true);
+        let bonus = board.hash();
+        assert_ne!(initial, second_player);
+        assert_ne!(second_player, bonus);
+        
+        board.set_turn(0, false);
+        assert_eq!(board.hash(), initial);
+    }
+    
+    #[test]
+    fn same_position_by_different_move_orders_hashes_equally() {
+        let mut first = Board::new(2);
+        first.move_piece(0, 0, 6);
+        first.move_piece(1, 0, 6);
+        first.move_piece(0, 0, 3);
+        
+        let mut second = Board::new(2);
+        second.move_piece(1, 0, 6);
+        second.move_piece(0, 0, 6);
+        second.move_piece(0, 0, 1);
+        second.move_piece(0, 0, 2);
+        
+        assert_eq!(first.hash(), second.hash());
+        assert_ne!(first.hash(), Board::new(2).hash());
+    }
+    
+    #[test]
+    fn capture_restores_the_victims_yard_hash() {
+        let mut board = Board::new(2);
+        board.move_piece(1, 0, 6);
+        let before_attacker = board.hash();
+        
+        // Player 0 walks round to player 1's start square, 26 steps on
+        board.move_piece(0, 1, 6);
+        for steps in [6, 6, 6, 2] {
+            assert_eq!(board.move_piece(0, 1, steps), MoveResult::Moved);
+        }
+        assert_eq!(board.move_piece(0, 1, 6), MoveResult::Captured);
+        assert!(board.is_in_yard(1, 0));
+        
+        let mut expected = Board::new(2);
+        expected.move_piece(0, 1, 6);
+        for steps in [6, 6, 6, 2, 6] {
+            expected.move_piece(0, 1, steps);
+        }
+        assert_eq!(board.hash(), expected.hash());
+        assert_ne!(board.hash(), before_attacker);
+    }
+    
+    #[test]
+    fn notation_round_trips_through_random_games() {
+        play_random_games(44, 100, |board| {
+            let notation = board.to_notation();
+            let parsed = Board::from_notation(&notation).unwrap();
+            assert_eq!(parsed.to_notation(), notation);
+            assert_eq!(parsed.hash(), board.hash());
+            assert_eq!(parsed.pieces, board.pieces);
+        });
+    }
+    
+    #[test]
+    fn notation_of_known_positions() {
+        assert_eq!(Board::new(2).to_notation(), "yyyy/yyyy 0 - classic");
+        
+        let mut board = Board::new(4);
+        board.move_piece(1, 2, 6);
+        board.move_piece(1, 2, 3);
+        board.set_piece_location(3, 0, PieceLocation::HomeTrack(4)).unwrap();
+        board.set_piece_location(3, 1, PieceLocation::Finished).unwrap();
+        board.set_turn(1, true);
+        assert_eq!(board.to_notation(), "yyyy/yyt16y/yyyy/h4fyy 1 b classic");
+        
+        let parsed = Board::from_notation("yyyy/yyt16y/yyyy/h4fyy 1 b classic").unwrap();
+        assert_eq!(parsed.get_piece_location(1, 2), PieceLocation::MainTrack(16));
+        assert_eq!(parsed.get_piece_location(3, 0), PieceLocation::HomeTrack(4));
+        assert!(parsed.is_finished(3, 1));
+        assert_eq!((parsed.to_move(), parsed.bonus_turn()), (1, true));
+    }
+    
+    #[test]
+    fn notation_rejects_malformed_positions() {
+        for text in [
+            "",
+            "yyyy/yyyy 0 -",
+            "yyyy/yyyy 0 - house-rules",
+            "yyyy/yyyy 2 - classic",
+            "yyyy/yyyy 0 x classic",
+            "yyy/yyyy 0 - classic",
+            "yyyyy/yyyy 0 - classic",
+            "yyyy 0 - classic",
+            "yyyy/yyyy/yyyy/yyyy/yyyy 0 - classic",
+            "tyyy/yyyy 0 - classic",
+            "t52yyy/yyyy 0 - classic",
+            "h6yyy/yyyy 0 - classic",
+            "h1h1yy/yyyy 0 - classic",
+            "y1yyy/yyyy 0 - classic",
+            "qyyy/yyyy 0 - classic",
+            // Player 0 turns into the home track before reaching square 51
+            "t51yyy/yyyy 0 - classic",
+        ] {
+            assert!(Board::from_notation(text).is_err(), "accepted '{}'", text);
+        }
+    }
+    
+    #[test]
+    fn threats_combine_each_opponents_hitting_rolls() {
+        // Player 1's pieces on 8 and 11 would turn into their home track
+        // before reaching square 13, so only player 2 threatens it
+        let board = Board::from_notation("t10t13h2y/t5t8t11f/t4t8yy 0 - classic").unwrap();
+        let threats = board.threats(0);
+        assert_eq!(threats.len(), 2);
+        
+        assert_eq!((threats[0].piece, threats[0].position), (Piece::new(0, 0), 10));
+        assert_eq!(threats[0].attackers.len(), 4);
+        // Player 1 hits with a 2 or a 5, player 2 with a 2 or a 6
+        assert!((threats[0].capture_probability - 5.0 / 9.0).abs() < 1e-9);
+        
+        assert_eq!((threats[1].piece, threats[1].position), (Piece::new(0, 1), 13));
+        assert_eq!(threats[1].attackers, [Attacker { piece: Piece::new(2, 1), distance: 5 }]);
+        assert!((threats[1].capture_probability - 1.0 / 6.0).abs() < 1e-9);
+        
+        // Two pieces of one player on the same distance are one hitting roll
+        let board = Board::from_notation("t10yyy/t4t4yy 0 - classic").unwrap();
+        assert!((board.threats(0)[0].capture_probability - 1.0 / 6.0).abs() < 1e-9);
+        assert!(Board::new(4).threats(0).is_empty());
+    }
+    
+    #[test]
+    fn render_marks_the_viewers_threatened_piece() {
+        // Player 1 has just come out onto square 26, where player 0 stands
+        // with another piece four squares behind
+        let board = Board::from_notation("t26t22yy/t26yyy 1 - classic").unwrap();
+        let colors: HashMap<PlayerId, colored::Color> =
+            [(0, colored::Color::Red), (1, colored::Color::Blue)].into_iter().collect();
+        let rendered = board.render(&colors, 1, None);
+        assert!(rendered.contains("P10!"), "{}", rendered);
+        assert!(!rendered.contains("P00"), "{}", rendered);
+        assert!(rendered.contains("Piece 0 on square 26: 17% chance"), "{}", rendered);
+    }
+}
@@ -0,0 +1,66 @@
+use crate::board::Board;
+use crate::endgame;
+use crate::engine::ExternalEngine;
+use crate::eval::{Features, Weights};
+use crate::player::PlayerId;
+
+// Anything that can pick a move for a player without asking the terminal
+pub trait PlayerController {
+    fn name(&self) -> &str;
+
+    // Pick one of `legal_moves` (never empty) for the given roll
+    fn choose_piece(&mut self, board: &Board, player_id: PlayerId, dice_value: u8, legal_moves: &[usize]) -> usize;
+}
+
+// Build a bot from a command-line or prompt spec: a personality name, a
+// weights file or `engine:COMMAND` for an external engine
+pub fn from_spec(spec: &str, name: &str) -> Result<Box<dyn PlayerController>, String> {
+    if let Some(command) = spec.strip_prefix("engine:") {
+        return Ok(Box::new(ExternalEngine::spawn(command)?));
+    }
+
+    let weights = Weights::load(spec)?;
+    Ok(Box::new(HeuristicBot::new(name.to_string(), weights)))
+}
+
+// Bot that plays the move leading to the best weighted evaluation
+pub struct HeuristicBot {
+    name: String,
+    weights: Weights,
+}
+
+impl HeuristicBot {
+    pub fn new(name: String, weights: Weights) -> Self {
+        HeuristicBot { name, weights }
+    }
+
+    // Score every legal move, best first
+    pub fn rank_moves(&self, board: &Board, player_id: PlayerId, dice_value: u8, legal_moves: &[usize]) -> Vec<(usize, f64)> {
+        let mut ranked: Vec<(usize, f64)> = legal_moves
+            .iter()
+            .map(|&piece_idx| {
+                let mut after = board.clone();
+                after.move_piece(player_id, piece_idx, dice_value as usize);
+                (piece_idx, self.weights.score(&Features::extract(&after, player_id)))
+            })
+            .collect();
+
+        // Stable sort keeps the lowest piece index first among equal scores
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranked
+    }
+}
+
+impl PlayerController for HeuristicBot {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    // Endgames covered by the tablebase are played perfectly
+    fn choose_piece(&mut self, board: &Board, player_id: PlayerId, dice_value: u8, legal_moves: &[usize]) -> usize {
+        if let Some(ranked) = endgame::default_tablebase().and_then(|tb| tb.rank_moves(board, player_id, dice_value, legal_moves)) {
+            return ranked[0].0;
+        }
+        self.rank_moves(board, player_id, dice_value, legal_moves)[0].0
+    }
+}
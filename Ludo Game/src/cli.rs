@@ -0,0 +1,37 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+// `--name value` options given after a subcommand
+pub struct Options {
+    values: HashMap<String, String>,
+}
+
+impl Options {
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut values = HashMap::new();
+        let mut iter = args.iter();
+
+        while let Some(arg) = iter.next() {
+            let name = arg
+                .strip_prefix("--")
+                .ok_or_else(|| format!("unexpected argument '{}'", arg))?;
+            let value = iter.next().ok_or_else(|| format!("option --{} needs a value", name))?;
+            values.insert(name.to_string(), value.clone());
+        }
+
+        Ok(Options { values })
+    }
+
+    pub fn get_str(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+
+    pub fn get_or<T: FromStr>(&self, name: &str, default: T) -> Result<T, String> {
+        match self.values.get(name) {
+            Some(value) => value
+                .parse()
+                .map_err(|_| format!("invalid value '{}' for --{}", value, name)),
+            None => Ok(default),
+        }
+    }
+}
@@ -1,17 +1,61 @@
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
+// Dice are always seeded, and count their rolls, so that a saved game can
+// bring them back to exactly the same state
 pub struct Dice {
     sides: u8,
+    seed: u64,
+    keyed: bool, // Rolling from a secret made from the seed, not the seed
+    rolls: u64,
+    rng: StdRng,
 }
 
 impl Dice {
     pub fn new(sides: u8) -> Self {
-        Dice { sides }
+        Self::seeded(sides, rand::random())
     }
     
-    pub fn roll(&self) -> u8 {
-        let mut rng = rand::thread_rng();
-        rng.gen_range(1..=self.sides)
+    // Dice that always produce the same sequence of rolls for a seed
+    pub fn seeded(sides: u8, seed: u64) -> Self {
+        Self::keyed(sides, seed, None)
+    }
+    
+    // Dice that roll from `secret` when there is one, but still report
+    // `seed`, so writing the seed down does not give the rolls away
+    pub fn keyed(sides: u8, seed: u64, secret: Option<u64>) -> Self {
+        let rng = StdRng::seed_from_u64(secret.unwrap_or(seed));
+        Dice { sides, seed, keyed: secret.is_some(), rolls: 0, rng }
+    }
+    
+    // Dice that have already been rolled `rolls` times
+    pub fn resume(sides: u8, seed: u64, secret: Option<u64>, rolls: u64) -> Self {
+        let mut dice = Self::keyed(sides, seed, secret);
+        for _ in 0..rolls {
+            dice.roll();
+        }
+        dice
+    }
+    
+    pub fn sides(&self) -> u8 {
+        self.sides
+    }
+    
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+    
+    pub fn is_keyed(&self) -> bool {
+        self.keyed
+    }
+    
+    pub fn rolls(&self) -> u64 {
+        self.rolls
+    }
+    
+    pub fn roll(&mut self) -> u8 {
+        self.rolls += 1;
+        self.rng.gen_range(1..=self.sides)
     }
 }
 
@@ -19,4 +63,13 @@ impl Default for Dice {
     fn default() -> Self {
         Self::new(6) // Standard 6-sided dice
     }
-}
\ No newline at end of file
+}
+
+// Mix a master seed with an index into an independent seed (SplitMix64),
+// so every game in a batch gets its own reproducible dice
+pub const fn derive_seed(master: u64, index: u64) -> u64 {
+    let mut z = master.wrapping_add(index.wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
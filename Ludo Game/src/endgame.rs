@@ -0,0 +1,409 @@
+use crate::board::{Board, PieceLocation, HOME_SPACES, MAIN_TRACK_SPACES, TOTAL_PIECES_PER_PLAYER};
+use crate::cli::Options;
+use crate::player::PlayerId;
+use crate::rollout;
+use std::fs;
+use std::sync::OnceLock;
+
+// Where `ludo tablebase` writes by default and where bots look for it
+pub const DEFAULT_PATH: &str = "endgame.tb";
+const MAGIC: &[u8; 8] = b"LUDOTB01";
+// Tables are solved for one or two pieces a side
+const MAX_PIECES: usize = 2;
+
+// Progress values used by the solver: 0 in the yard, 1..=47 on the main
+// track (distance from the start square plus one), 48..=53 on the home
+// track. Finished pieces are simply left out.
+const STEPS: usize = MAIN_TRACK_SPACES + 2;
+const HOME_START: usize = MAIN_TRACK_SPACES - HOME_SPACES + 2;
+// In a two-player game the second player starts half-way round
+const OPPONENT_OFFSET: usize = MAIN_TRACK_SPACES / 2;
+
+// Exact win probabilities for two-player endgames where each side has at
+// most `max_pieces` pieces left to bring home. Values are for the player to
+// move, indexed by (own pieces, opponent pieces); the board is symmetric, so
+// that is all a position needs.
+pub struct Tablebase {
+    max_pieces: usize,
+    values: Vec<f32>,
+}
+
+impl Tablebase {
+    // Solve all positions by value iteration. Captures send pieces back, so
+    // positions can repeat and the values have to be iterated to a fixed
+    // point rather than computed backwards in one pass.
+    pub fn solve(max_pieces: usize, mut progress: impl FnMut(usize, f64)) -> Self {
+        let lists = piece_lists(max_pieces);
+        let count = lists.len();
+        let mut values = vec![0.5f64; count * count];
+
+        for sweep in 1.. {
+            let mut largest_change: f64 = 0.0;
+
+            for (own_idx, own) in lists.iter().enumerate() {
+                for (other_idx, other) in lists.iter().enumerate() {
+                    let state = own_idx * count + other_idx;
+                    let value = position_value(own, other, &values, count);
+                    largest_change = largest_change.max((value - values[state]).abs());
+                    values[state] = value;
+                }
+            }
+
+            progress(sweep, largest_change);
+            if largest_change < 1e-9 {
+                break;
+            }
+        }
+
+        Tablebase {
+            max_pieces,
+            values: values.into_iter().map(|v| v as f32).collect(),
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let bytes = fs::read(path).map_err(|err| format!("cannot read tablebase '{}': {}", path, err))?;
+        if bytes.len() < MAGIC.len() + 1 || &bytes[..MAGIC.len()] != MAGIC {
+            return Err(format!("'{}' is not a tablebase file", path));
+        }
+
+        let max_pieces = bytes[MAGIC.len()] as usize;
+        if !(1..=MAX_PIECES).contains(&max_pieces) {
+            return Err(format!("tablebase '{}' is truncated or corrupt", path));
+        }
+        let count = piece_lists(max_pieces).len();
+        let data = &bytes[MAGIC.len() + 1..];
+        if data.len() != count * count * 4 {
+            return Err(format!("tablebase '{}' is truncated or corrupt", path));
+        }
+
+        let values = data
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect();
+        Ok(Tablebase { max_pieces, values })
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(self.max_pieces as u8);
+        for value in &self.values {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        fs::write(path, bytes).map_err(|err| format!("cannot write tablebase '{}': {}", path, err))
+    }
+
+    // Exact chance that `to_move` wins with the other player to follow, or
+    // None if the position is not in the table
+    pub fn probe(&self, board: &Board, to_move: PlayerId) -> Option<f64> {
+        if board.player_count() != 2 {
+            return None;
+        }
+        let own = pieces_left(board, to_move, self.max_pieces)?;
+        let other = pieces_left(board, 1 - to_move, self.max_pieces)?;
+        if own.is_empty() {
+            return Some(1.0);
+        }
+        if other.is_empty() {
+            return Some(0.0);
+        }
+        Some(self.values[list_index(&own) * piece_lists(self.max_pieces).len() + list_index(&other)] as f64)
+    }
+
+    // Exact win probability for the mover after each legal move, best
+    // first, or None if the position is not in the table
+    pub fn rank_moves(&self, board: &Board, player_id: PlayerId, dice_value: u8, legal_moves: &[usize]) -> Option<Vec<(usize, f64)>> {
+        self.probe(board, player_id)?;
+
+        let mut ranked = Vec::new();
+        for &piece_idx in legal_moves {
+            let mut after = board.clone();
+            let result = after.move_piece(player_id, piece_idx, dice_value as usize);
+            let value = if after.has_won(player_id) {
+                1.0
+            } else if rollout::next_to_move(&after, player_id, &result, dice_value, &[]) == player_id {
+                self.probe(&after, player_id)?
+            } else {
+                1.0 - self.probe(&after, 1 - player_id)?
+            };
+            ranked.push((piece_idx, value));
+        }
+
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        Some(ranked)
+    }
+}
+
+// The tablebase at DEFAULT_PATH, loaded on first use, if there is one
+pub fn default_tablebase() -> Option<&'static Tablebase> {
+    static TABLEBASE: OnceLock<Option<Tablebase>> = OnceLock::new();
+    TABLEBASE.get_or_init(|| Tablebase::load(DEFAULT_PATH).ok()).as_ref()
+}
+
+// `ludo tablebase`: solve endgames and write the table to disk
+pub fn run(options: &Options) -> Result<(), String> {
+    let max_pieces = options.get_or("pieces", 2usize)?;
+    let out = options.get_str("out").unwrap_or(DEFAULT_PATH);
+
+    if !(1..=MAX_PIECES).contains(&max_pieces) {
+        return Err(format!("--pieces must be 1 to {}", MAX_PIECES));
+    }
+
+    let count = piece_lists(max_pieces).len();
+    println!("Solving {} positions with up to {} piece(s) a side...", count * count, max_pieces);
+    let tablebase = Tablebase::solve(max_pieces, |sweep, change| {
+        if sweep % 10 == 0 {
+            println!("  sweep {}: largest change {:.2e}", sweep, change);
+        }
+    });
+    tablebase.save(out)?;
+    println!("Tablebase written to {}", out);
+    Ok(())
+}
+
+// Chance that the player to move wins, given the current estimates for
+// every other position: the average over the six rolls of the best move.
+// A roll with no legal move passes the turn.
+fn position_value(own: &[u8], other: &[u8], values: &[f64], count: usize) -> f64 {
+    let mut total = 0.0;
+
+    for roll in 1..=6 {
+        let mut best: Option<f64> = None;
+
+        for (i, &piece) in own.iter().enumerate() {
+            if i > 0 && own[i - 1] == piece {
+                continue; // Same square, same move
+            }
+            let (target, captured) = match step(own, other, piece, roll) {
+                Some(result) => result,
+                None => continue,
+            };
+
+            let mut new_own = [0u8; 2];
+            let mut own_len = 0;
+            for (j, &p) in own.iter().enumerate() {
+                if j != i {
+                    new_own[own_len] = p;
+                    own_len += 1;
+                }
+            }
+            if let Some(target) = target {
+                new_own[own_len] = target;
+                own_len += 1;
+            }
+            let new_own = sorted(&mut new_own[..own_len]);
+
+            let mut new_other = [0u8; 2];
+            let mut capture_count = 0;
+            for (j, &p) in other.iter().enumerate() {
+                new_other[j] = if p == captured { 0 } else { p };
+                if p == captured && captured != 0 {
+                    capture_count += 1;
+                }
+            }
+            let new_other = sorted(&mut new_other[..other.len()]);
+
+            let value = if new_own.is_empty() {
+                1.0
+            } else if roll == 6 || capture_count > 0 {
+                values[list_index(new_own) * count + list_index(new_other)]
+            } else {
+                1.0 - values[list_index(new_other) * count + list_index(new_own)]
+            };
+            best = Some(best.map_or(value, |b: f64| b.max(value)));
+        }
+
+        total += best.unwrap_or_else(|| 1.0 - values[list_index(other) * count + list_index(own)]);
+    }
+
+    total / 6.0
+}
+
+fn sorted(list: &mut [u8]) -> &[u8] {
+    list.sort_unstable();
+    list
+}
+
+// Where a piece goes with a roll (None for finished) and the progress value
+// of the opponent pieces it captures (0 for none), following the same rules
+// as `Board::move_piece`. Returns None for an illegal move.
+fn step(own: &[u8], other: &[u8], piece: u8, roll: usize) -> Option<(Option<u8>, u8)> {
+    let piece = piece as usize;
+
+    if piece == 0 {
+        // Out of the yard onto the start square; entering never captures
+        return if roll == 6 { Some((Some(1), 0)) } else { None };
+    }
+
+    if piece >= HOME_START {
+        let target = piece + roll;
+        return if target == STEPS {
+            Some((None, 0))
+        } else if target < STEPS && !own.contains(&(target as u8)) {
+            Some((Some(target as u8), 0))
+        } else {
+            None
+        };
+    }
+
+    // On the main track. A piece one short of the home track that rolls past
+    // the end of the board wraps round to its start square, as on `Board`.
+    let distance = (piece - 1 + roll) % MAIN_TRACK_SPACES;
+    let home_entry_distance = MAIN_TRACK_SPACES - HOME_SPACES;
+    if distance > home_entry_distance {
+        let target = HOME_START + distance - home_entry_distance - 1;
+        return if !own.contains(&(target as u8)) { Some((Some(target as u8), 0)) } else { None };
+    }
+
+    // Opponent pieces on this square all share one progress value
+    let victim = (distance + MAIN_TRACK_SPACES - OPPONENT_OFFSET) % MAIN_TRACK_SPACES + 1;
+    let captured = if victim < HOME_START && other.contains(&(victim as u8)) { victim as u8 } else { 0 };
+    Some((Some(distance as u8 + 1), captured))
+}
+
+// Every sorted list of 1..=max_pieces progress values, in index order
+fn piece_lists(max_pieces: usize) -> Vec<Vec<u8>> {
+    let mut lists: Vec<Vec<u8>> = (0..STEPS as u8).map(|p| vec![p]).collect();
+    if max_pieces >= 2 {
+        for b in 0..STEPS as u8 {
+            for a in 0..=b {
+                lists.push(vec![a, b]);
+            }
+        }
+    }
+    lists
+}
+
+fn list_index(list: &[u8]) -> usize {
+    match *list {
+        [p] => p as usize,
+        [a, b] => STEPS + (b as usize) * (b as usize + 1) / 2 + a as usize,
+        _ => unreachable!("tablebase positions have one or two pieces a side"),
+    }
+}
+
+// A player's unfinished pieces as sorted progress values, if there are few
+// enough of them for the table
+fn pieces_left(board: &Board, player_id: PlayerId, max_pieces: usize) -> Option<Vec<u8>> {
+    let mut pieces: Vec<u8> = (0..TOTAL_PIECES_PER_PLAYER)
+        .filter(|&piece_idx| board.get_piece_location(player_id, piece_idx) != PieceLocation::Finished)
+        .map(|piece_idx| board.progress(player_id, piece_idx) as u8)
+        .collect();
+    if pieces.len() > max_pieces {
+        return None;
+    }
+    pieces.sort_unstable();
+    Some(pieces)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn one_piece_table() -> &'static Tablebase {
+        static TABLE: OnceLock<Tablebase> = OnceLock::new();
+        TABLE.get_or_init(|| Tablebase::solve(1, |_, _| {}))
+    }
+
+    fn position(notation: &str) -> Board {
+        Board::from_notation(notation).unwrap()
+    }
+
+    #[test]
+    fn trivial_endgames_have_exact_values() {
+        let table = one_piece_table();
+
+        // Whoever has finished has won, whatever the other side has left
+        assert_eq!(table.probe(&position("ffff/t30fff 0 - classic"), 0), Some(1.0));
+        assert_eq!(table.probe(&position("ffff/t30fff 0 - classic"), 1), Some(0.0));
+
+        // Both need a 1 to finish: the mover wins with 1/6 + 5/6 of the
+        // chance of the other side failing, 1 / (2 - 1/6) = 6/11
+        let race = table.probe(&position("h5fff/h5fff 0 - classic"), 0).unwrap();
+        assert!((race - 6.0 / 11.0).abs() < 1e-6, "{}", race);
+
+        // A piece one from home against one still in the yard
+        let nearly = table.probe(&position("h5fff/yfff 0 - classic"), 0).unwrap();
+        assert!(nearly > 0.95 && nearly < 1.0, "{}", nearly);
+        let hopeless = table.probe(&position("h5fff/yfff 1 - classic"), 1).unwrap();
+        assert!(hopeless > 0.0 && hopeless < 0.05, "{}", hopeless);
+
+        // Too many pieces, or too many players, for the table
+        assert_eq!(table.probe(&position("h5h4ff/yfff 0 - classic"), 0), None);
+        assert_eq!(table.probe(&position("h5fff/yfff/ffff 0 - classic"), 0), None);
+    }
+
+    // Tables come back from disk as they were saved, and files with a
+    // piece count the solver never writes are refused rather than probed
+    #[test]
+    fn load_checks_the_file() {
+        let path = std::env::temp_dir().join(format!("ludo-endgame-{}.tb", std::process::id()));
+        let path = path.to_str().unwrap();
+        let table = one_piece_table();
+        table.save(path).unwrap();
+        let loaded = Tablebase::load(path).unwrap();
+        assert_eq!(loaded.max_pieces, 1);
+        assert_eq!(loaded.values, table.values);
+
+        let two_piece_data = piece_lists(2).len().pow(2) * 4;
+        for (pieces, data) in [(0, table.values.len() * 4), (3, two_piece_data), (255, two_piece_data)] {
+            let mut bytes = MAGIC.to_vec();
+            bytes.push(pieces);
+            bytes.resize(bytes.len() + data, 0);
+            fs::write(path, bytes).unwrap();
+            let err = Tablebase::load(path).err().expect("bad piece counts are refused");
+            assert!(err.contains("corrupt"), "{}", err);
+        }
+
+        fs::write(path, b"not a table").unwrap();
+        assert!(Tablebase::load(path).is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    // With one piece a side there is never a choice to make, so random
+    // rollouts estimate the exact values, and they follow `Board`'s rules
+    // rather than the solver's own
+    #[test]
+    fn values_agree_with_rollouts() {
+        let table = one_piece_table();
+        let mut rng = StdRng::seed_from_u64(37);
+        let rollouts = 4000;
+        let tolerance = 4.0 * (0.25 / rollouts as f64).sqrt();
+
+        for notation in [
+            "t20fff/t30fff 0 - classic",
+            "yfff/t10fff 0 - classic",
+            "t40fff/h2fff 1 - classic",
+            "t45fff/t15fff 0 - classic",
+        ] {
+            let board = position(notation);
+            let to_move = board.to_move();
+            let exact = table.probe(&board, to_move).unwrap();
+            let estimate = rollout::win_probabilities(&board, to_move, &[], rollouts, &mut rng)[to_move];
+            assert!((exact - estimate).abs() < tolerance, "{}: {} exact, {} by rollouts", notation, exact, estimate);
+
+            for dice_value in 1..=6u8 {
+                let legal_moves = board.legal_moves(to_move, dice_value as usize);
+                let Some(ranked) = table.rank_moves(&board, to_move, dice_value, &legal_moves) else {
+                    panic!("{} is in the table", notation);
+                };
+                for (piece_idx, value) in ranked {
+                    let mut after = board.clone();
+                    let result = after.move_piece(to_move, piece_idx, dice_value as usize);
+                    let next = rollout::next_to_move(&after, to_move, &result, dice_value, &[]);
+                    let estimate = rollout::win_probabilities(&after, next, &[], rollouts, &mut rng)[to_move];
+                    assert!(
+                        (value - estimate).abs() < tolerance,
+                        "{} rolling {}: {} exact, {} by rollouts",
+                        notation,
+                        dice_value,
+                        value,
+                        estimate
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,256 @@
+use crate::board::{Board, PieceLocation, TOTAL_PIECES_PER_PLAYER};
+use crate::bot::PlayerController;
+use crate::player::PlayerId;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+pub const PROTOCOL_VERSION: u32 = 1;
+const DEFAULT_TIMEOUT_MS: u64 = 2000;
+
+// A bot running as a child process that speaks the line-based engine
+// protocol (see PROTOCOL.md) over its stdin and stdout
+pub struct ExternalEngine {
+    name: String,
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    timeout: Duration,
+    // Replies to `go` that were given up on. The engine still owes them and
+    // they come first, so they are skipped rather than taken as answers to
+    // the position being asked about.
+    late_replies: usize,
+}
+
+impl ExternalEngine {
+    // `spec` is the part after `engine:`, either `COMMAND` or `MS:COMMAND`
+    // to set the time allowed per reply in milliseconds
+    pub fn spawn(spec: &str) -> Result<Self, String> {
+        let (timeout_ms, command_line) = match spec.split_once(':') {
+            Some((ms, rest)) if !ms.is_empty() && ms.chars().all(|c| c.is_ascii_digit()) => {
+                (ms.parse::<u64>().map_err(|_| format!("invalid engine timeout '{}'", ms))?, rest)
+            }
+            _ => (DEFAULT_TIMEOUT_MS, spec),
+        };
+
+        let mut words = command_line.split_whitespace();
+        let program = words.next().ok_or("engine spec needs a command")?;
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|err| format!("cannot start engine '{}': {}", command_line, err))?;
+
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        // Read on a separate thread so a silent engine can be timed out
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(line) => {
+                        if sender.send(line).is_err() {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+
+        let mut engine = ExternalEngine {
+            name: program.to_string(),
+            child,
+            stdin,
+            lines,
+            timeout: Duration::from_millis(timeout_ms),
+            late_replies: 0,
+        };
+
+        engine.send(&format!("ludo {}", PROTOCOL_VERSION))?;
+        let reply = engine.receive()?;
+        let name = reply
+            .strip_prefix("ready")
+            .ok_or_else(|| format!("engine '{}' answered '{}' instead of 'ready'", command_line, reply))?
+            .trim();
+        if !name.is_empty() {
+            engine.name = name.to_string();
+        }
+
+        Ok(engine)
+    }
+
+    fn send(&mut self, line: &str) -> Result<(), String> {
+        writeln!(self.stdin, "{}", line)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|err| format!("engine '{}' stopped listening: {}", self.name, err))
+    }
+
+    fn receive(&mut self) -> Result<String, String> {
+        self.lines
+            .recv_timeout(self.timeout)
+            .map(|line| line.trim().to_string())
+            .map_err(|_| format!("engine '{}' did not answer within {:?}", self.name, self.timeout))
+    }
+
+    fn ask_for_move(&mut self, board: &Board, player_id: PlayerId, dice_value: u8, legal_moves: &[usize]) -> Result<usize, String> {
+        let moves: Vec<String> = legal_moves.iter().map(|piece_idx| piece_idx.to_string()).collect();
+
+        self.send(&format!("position {}", encode_position(board, player_id)))?;
+        self.send(&format!("roll {}", dice_value))?;
+        self.send(&format!("moves {}", moves.join(" ")))?;
+        self.send("go")?;
+
+        let reply = loop {
+            let reply = self.receive().inspect_err(|_| self.late_replies += 1)?;
+            if self.late_replies > 0 && reply.starts_with("move") {
+                self.late_replies -= 1;
+            } else {
+                break reply;
+            }
+        };
+        let piece_idx = reply
+            .strip_prefix("move ")
+            .and_then(|piece| piece.trim().parse::<usize>().ok())
+            .ok_or_else(|| format!("engine '{}' sent '{}' instead of 'move <piece>'", self.name, reply))?;
+
+        if legal_moves.contains(&piece_idx) {
+            Ok(piece_idx)
+        } else {
+            Err(format!("engine '{}' chose illegal piece {}", self.name, piece_idx))
+        }
+    }
+}
+
+impl PlayerController for ExternalEngine {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    // A timed-out, crashed or confused engine forfeits its choice and the
+    // first legal move is played instead, so the game can go on
+    fn choose_piece(&mut self, board: &Board, player_id: PlayerId, dice_value: u8, legal_moves: &[usize]) -> usize {
+        match self.ask_for_move(board, player_id, dice_value, legal_moves) {
+            Ok(piece_idx) => piece_idx,
+            Err(err) => {
+                eprintln!("warning: {}; playing piece {}", err, legal_moves[0]);
+                legal_moves[0]
+            }
+        }
+    }
+}
+
+impl Drop for ExternalEngine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// `<players> <to move> <pieces of player 0> <pieces of player 1> ...`, where
+// each player's pieces are a comma-separated list of `y` (yard), `t<square>`
+// (main track), `h<step>` (home track) or `f` (finished)
+pub fn encode_position(board: &Board, to_move: PlayerId) -> String {
+    let mut fields = vec![board.player_count().to_string(), to_move.to_string()];
+
+    for player_id in 0..board.player_count() {
+        let pieces: Vec<String> = (0..TOTAL_PIECES_PER_PLAYER)
+            .map(|piece_idx| match board.get_piece_location(player_id, piece_idx) {
+                PieceLocation::Yard => "y".to_string(),
+                PieceLocation::MainTrack(pos) => format!("t{}", pos),
+                PieceLocation::HomeTrack(pos) => format!("h{}", pos),
+                PieceLocation::Finished => "f".to_string(),
+            })
+            .collect();
+        fields.push(pieces.join(","));
+    }
+
+    fields.join(" ")
+}
+
+// The reverse of `encode_position`
+pub fn decode_position(text: &str) -> Result<(Board, PlayerId), String> {
+    let fields: Vec<&str> = text.split_whitespace().collect();
+    let player_count = match fields.first().and_then(|field| field.parse::<usize>().ok()) {
+        Some(count) if (2..=4).contains(&count) => count,
+        _ => return Err(format!("position '{}' must start with a player count of 2 to 4", text)),
+    };
+    if fields.len() != player_count + 2 {
+        return Err(format!("position '{}' needs a player to move and pieces for {} players", text, player_count));
+    }
+    let to_move = match fields[1].parse::<PlayerId>() {
+        Ok(player_id) if player_id < player_count => player_id,
+        _ => return Err(format!("invalid player to move '{}'", fields[1])),
+    };
+
+    let mut board = Board::new(player_count);
+    for (player_id, field) in fields[2..].iter().enumerate() {
+        let pieces: Vec<&str> = field.split(',').collect();
+        if pieces.len() != TOTAL_PIECES_PER_PLAYER {
+            return Err(format!("player {} needs {} pieces, not '{}'", player_id, TOTAL_PIECES_PER_PLAYER, field));
+        }
+        for (piece_idx, piece) in pieces.iter().enumerate() {
+            let number = |digits: &str| digits.parse::<usize>().map_err(|_| format!("invalid piece '{}'", piece));
+            let location = match (piece.get(..1), piece.get(1..)) {
+                (Some("y"), Some("")) => PieceLocation::Yard,
+                (Some("f"), Some("")) => PieceLocation::Finished,
+                (Some("t"), Some(digits)) => PieceLocation::MainTrack(number(digits)?),
+                (Some("h"), Some(digits)) => PieceLocation::HomeTrack(number(digits)?),
+                _ => return Err(format!("invalid piece '{}'", piece)),
+            };
+            board.set_piece_location(player_id, piece_idx, location)?;
+        }
+    }
+    board.set_turn(to_move, false);
+
+    Ok((board, to_move))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    // An engine that always moves the last listed piece, but takes longer
+    // than it is allowed over its first answer
+    const SLOW_ENGINE: &str = "#!/bin/sh
+answered=0
+while read -r command rest; do
+    case $command in
+        ludo) echo 'ready slow' ;;
+        moves) last=${rest##* } ;;
+        go)
+            if [ $answered -eq 0 ]; then sleep 2; fi
+            answered=1
+            echo \"move $last\" ;;
+        quit) exit 0 ;;
+    esac
+done
+";
+
+    #[test]
+    fn late_replies_are_not_taken_for_later_moves() {
+        let script = env::temp_dir().join(format!("ludo-slow-engine-{}.sh", std::process::id()));
+        fs::write(&script, SLOW_ENGINE).unwrap();
+        // Plenty of time to start, however busy the machine is
+        let engine = ExternalEngine::spawn(&format!("10000:sh {}", script.display()));
+        fs::remove_file(&script).unwrap();
+        let mut engine = engine.unwrap();
+        assert_eq!(engine.name(), "slow");
+
+        let board = Board::new(2);
+        // Timed out: the first legal move is played for it
+        engine.timeout = Duration::from_millis(100);
+        assert_eq!(engine.choose_piece(&board, 0, 6, &[0, 1]), 0);
+        // The answer to the first position arrives while this one is asked
+        engine.timeout = Duration::from_millis(10000);
+        assert_eq!(engine.choose_piece(&board, 0, 6, &[1, 2]), 2);
+        assert_eq!(engine.choose_piece(&board, 0, 6, &[0, 3]), 3);
+    }
+}
@@ -0,0 +1,291 @@
+use crate::board::{Board, PieceLocation, MAIN_TRACK_SPACES, PATH_LENGTH, TOTAL_PIECES_PER_PLAYER};
+use crate::player::PlayerId;
+use std::fs;
+
+// Named features the heuristic bot looks at when scoring a position
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    PiecesHome,
+    Progress,
+    PiecesInDanger,
+    CapturesAvailable,
+    SafePieces,
+    Blockades,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 6] = [
+        Feature::PiecesHome,
+        Feature::Progress,
+        Feature::PiecesInDanger,
+        Feature::CapturesAvailable,
+        Feature::SafePieces,
+        Feature::Blockades,
+    ];
+
+    // Key used for this feature in weight files
+    pub fn key(self) -> &'static str {
+        match self {
+            Feature::PiecesHome => "pieces_home",
+            Feature::Progress => "progress",
+            Feature::PiecesInDanger => "pieces_in_danger",
+            Feature::CapturesAvailable => "captures_available",
+            Feature::SafePieces => "safe_pieces",
+            Feature::Blockades => "blockades",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Feature> {
+        Feature::ALL.iter().copied().find(|feature| feature.key() == key)
+    }
+
+    fn index(self) -> usize {
+        Feature::ALL.iter().position(|&feature| feature == self).unwrap()
+    }
+}
+
+// Feature values of a position, seen from one player's side
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Features {
+    values: [f64; Feature::ALL.len()],
+}
+
+impl Features {
+    pub fn extract(board: &Board, player_id: PlayerId) -> Self {
+        let opponents: Vec<PlayerId> = (0..board.player_count()).filter(|&pid| pid != player_id).collect();
+        let mut values = [0.0; Feature::ALL.len()];
+
+        // Pieces home and progress are measured against the average opponent,
+        // so sending someone back to their yard counts in our favour
+        let opponent_average = |measure: &dyn Fn(PlayerId) -> f64| {
+            opponents.iter().map(|&pid| measure(pid)).sum::<f64>() / opponents.len().max(1) as f64
+        };
+        let finished = |pid: PlayerId| {
+            (0..TOTAL_PIECES_PER_PLAYER).filter(|&idx| board.is_finished(pid, idx)).count() as f64
+        };
+        let progress = |pid: PlayerId| {
+            (0..TOTAL_PIECES_PER_PLAYER).map(|idx| board.progress(pid, idx)).sum::<usize>() as f64 / PATH_LENGTH as f64
+        };
+        values[Feature::PiecesHome.index()] = finished(player_id) - opponent_average(&finished);
+        values[Feature::Progress.index()] = progress(player_id) - opponent_average(&progress);
+
+        for piece_idx in 0..TOTAL_PIECES_PER_PLAYER {
+            match board.get_piece_location(player_id, piece_idx) {
+                PieceLocation::MainTrack(pos) if !board.attackers(player_id, pos).is_empty() => {
+                    values[Feature::PiecesInDanger.index()] += 1.0;
+                }
+                // There are no protected squares on the main track in these
+                // rules, so only the home track is out of reach
+                PieceLocation::HomeTrack(_) => values[Feature::SafePieces.index()] += 1.0,
+                _ => {}
+            }
+        }
+
+        for pos in 0..MAIN_TRACK_SPACES {
+            let mut own = 0;
+            let mut targets = 0;
+            for piece in board.pieces_at(pos) {
+                if piece.player_id == player_id {
+                    own += 1;
+                } else if board.attackers(piece.player_id, pos).iter().any(|attacker| attacker.piece.player_id == player_id) {
+                    targets += 1;
+                }
+            }
+            if own >= 2 {
+                values[Feature::Blockades.index()] += 1.0;
+            }
+            values[Feature::CapturesAvailable.index()] += targets as f64;
+        }
+
+        Features { values }
+    }
+
+    pub fn get(&self, feature: Feature) -> f64 {
+        self.values[feature.index()]
+    }
+}
+
+// How much the bot cares about each feature
+#[derive(Debug, Clone, PartialEq)]
+pub struct Weights {
+    values: [f64; Feature::ALL.len()],
+}
+
+impl Weights {
+    // Built-in personalities, shipped as files under `personalities/`
+    pub const PRESETS: [(&'static str, &'static str); 4] = [
+        ("balanced", include_str!("../personalities/balanced.cfg")),
+        ("aggressive", include_str!("../personalities/aggressive.cfg")),
+        ("defensive", include_str!("../personalities/defensive.cfg")),
+        ("racing", include_str!("../personalities/racing.cfg")),
+    ];
+
+    pub fn preset(name: &str) -> Option<Weights> {
+        Self::PRESETS
+            .iter()
+            .find(|(preset, _)| preset.eq_ignore_ascii_case(name))
+            .map(|(_, text)| Self::parse(text).expect("built-in personality should parse"))
+    }
+
+    // Accepts either a preset name or a path to a weights file
+    pub fn load(spec: &str) -> Result<Weights, String> {
+        if let Some(weights) = Self::preset(spec) {
+            return Ok(weights);
+        }
+        let text = fs::read_to_string(spec).map_err(|err| format!("cannot read weights file '{}': {}", spec, err))?;
+        Self::parse(&text).map_err(|err| format!("{}: {}", spec, err))
+    }
+
+    // Parse `feature = weight` lines. Blank lines and `#` comments are
+    // ignored, and features that are not listed get a weight of zero.
+    pub fn parse(text: &str) -> Result<Weights, String> {
+        let mut weights = Weights { values: [0.0; Feature::ALL.len()] };
+
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected 'feature = weight'", line_no + 1))?;
+            let feature = Feature::from_key(key.trim())
+                .ok_or_else(|| format!("line {}: unknown feature '{}'", line_no + 1, key.trim()))?;
+            let value = value
+                .trim()
+                .parse::<f64>()
+                .map_err(|_| format!("line {}: '{}' is not a number", line_no + 1, value.trim()))?;
+            weights.set(feature, value);
+        }
+
+        Ok(weights)
+    }
+
+    pub fn get(&self, feature: Feature) -> f64 {
+        self.values[feature.index()]
+    }
+
+    pub fn set(&mut self, feature: Feature, value: f64) {
+        self.values[feature.index()] = value;
+    }
+
+    // One-line summary, e.g. for progress output
+    pub fn to_line(&self) -> String {
+        Feature::ALL
+            .iter()
+            .map(|&feature| format!("{}={:.3}", feature.key(), self.get(feature)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    // Write the weights in the same format `parse` reads
+    pub fn save(&self, path: &str, comment: &str) -> Result<(), String> {
+        let mut text = format!("# {}\n", comment);
+        for &feature in Feature::ALL.iter() {
+            text.push_str(&format!("{} = {:.4}\n", feature.key(), self.get(feature)));
+        }
+        fs::write(path, text).map_err(|err| format!("cannot write weights file '{}': {}", path, err))
+    }
+
+    pub fn score(&self, features: &Features) -> f64 {
+        Feature::ALL.iter().map(|&feature| self.get(feature) * features.get(feature)).sum()
+    }
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self::preset("balanced").unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn features(notation: &str, player_id: PlayerId) -> Features {
+        Features::extract(&Board::from_notation(notation).unwrap(), player_id)
+    }
+
+    #[test]
+    fn starting_position_has_no_features() {
+        for player_count in 2..=4 {
+            let board = Board::new(player_count);
+            for player_id in 0..player_count {
+                assert_eq!(Features::extract(&board, player_id).values, [0.0; Feature::ALL.len()]);
+            }
+        }
+    }
+
+    #[test]
+    fn features_of_a_known_position() {
+        // Player 0 has two pieces on square 10, within reach of both of
+        // player 1's pieces behind it, one on the home track and one home
+        let position = "t10t10h1f/t5t8yy 0 - classic";
+
+        let ours = features(position, 0);
+        assert_eq!(ours.get(Feature::PiecesHome), 1.0);
+        assert_eq!(ours.get(Feature::PiecesInDanger), 2.0);
+        assert_eq!(ours.get(Feature::CapturesAvailable), 0.0);
+        assert_eq!(ours.get(Feature::SafePieces), 1.0);
+        assert_eq!(ours.get(Feature::Blockades), 1.0);
+        // 11 + 11 + 49 + 54 steps against 32 + 35
+        assert!((ours.get(Feature::Progress) - 58.0 / PATH_LENGTH as f64).abs() < 1e-9);
+
+        let theirs = features(position, 1);
+        assert_eq!(theirs.get(Feature::PiecesHome), -1.0);
+        assert_eq!(theirs.get(Feature::PiecesInDanger), 0.0);
+        assert_eq!(theirs.get(Feature::CapturesAvailable), 2.0);
+        assert_eq!(theirs.get(Feature::SafePieces), 0.0);
+        assert_eq!(theirs.get(Feature::Blockades), 0.0);
+        assert!((theirs.get(Feature::Progress) + ours.get(Feature::Progress)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn home_and_progress_are_measured_against_the_average_opponent() {
+        let ours = features("ffyy/fyyy/yyyy 0 - classic", 0);
+        assert_eq!(ours.get(Feature::PiecesHome), 1.5);
+        assert!((ours.get(Feature::Progress) - 1.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn weights_parse_and_score() {
+        let weights = Weights::parse("# Comment\n\nprogress = 2.5  # trailing comment\n  blockades=-1\n").unwrap();
+        assert_eq!(weights.get(Feature::Progress), 2.5);
+        assert_eq!(weights.get(Feature::Blockades), -1.0);
+        assert_eq!(weights.get(Feature::PiecesHome), 0.0);
+
+        let position = features("t10t10h1f/t5t8yy 0 - classic", 0);
+        let expected = 2.5 * position.get(Feature::Progress) - position.get(Feature::Blockades);
+        assert!((weights.score(&position) - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn weights_reject_malformed_files() {
+        for (text, error) in [
+            ("progress 2.5", "line 1: expected 'feature = weight'"),
+            ("\nluck = 1", "line 2: unknown feature 'luck'"),
+            ("progress = lots", "line 1: 'lots' is not a number"),
+        ] {
+            assert_eq!(Weights::parse(text), Err(error.to_string()));
+        }
+    }
+
+    #[test]
+    fn presets_load_by_name_and_saved_weights_read_back() {
+        for (name, _) in Weights::PRESETS {
+            let weights = Weights::load(&name.to_uppercase()).unwrap();
+            assert_ne!(weights, Weights { values: [0.0; Feature::ALL.len()] }, "{}", name);
+        }
+        assert!(Weights::load("no-such-personality.cfg").is_err());
+
+        let mut weights = Weights::default();
+        weights.set(Feature::SafePieces, -0.125);
+        let path = std::env::temp_dir().join(format!("ludo-weights-{}.cfg", std::process::id()));
+        let path = path.to_str().unwrap();
+        weights.save(path, "written by a test").unwrap();
+        let loaded = Weights::load(path);
+        fs::remove_file(path).unwrap();
+        assert_eq!(loaded, Ok(weights));
+    }
+}
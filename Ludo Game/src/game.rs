@@ -1,137 +1,998 @@
-use crate::board::{Board, MoveResult, TOTAL_PIECES_PER_PLAYER};
+use crate::advisor;
+use crate::analysis;
+use crate::board::{Board, MoveResult, PieceLocation, TOTAL_PIECES_PER_PLAYER};
+use crate::bot::{self, PlayerController};
 use crate::dice::Dice;
-use crate::player::{Piece, Player, PlayerId};
-use crate::ui::UI;
+use crate::player::{Piece, Player, PlayerId, PLAYER_COLORS};
+use crate::record::{self, GameRecord, RecordedMove, TurnRecord};
+use crate::rollout::{self, DEFAULT_ROLLOUTS};
+use crate::save::{self, SavedGame, SavedPlayer};
+use crate::seal::{self, Seal};
+use crate::ui::{PieceChoice, Quit, QuitChoice, RollChoice, UI};
 use colored::Color;
 use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+
+// Counters collected while a game is played
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GameStats {
+    pub turns: usize,
+    pub captures: usize,
+    pub sixes: usize,
+}
+
+// How the first player is picked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartRule {
+    FirstSeat, // Player 1 always starts
+    RollOff,   // Everyone rolls, highest starts, ties roll again
+}
+
+impl FromStr for StartRule {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "first" => Ok(StartRule::FirstSeat),
+            "rolloff" => Ok(StartRule::RollOff),
+            _ => Err(format!("unknown start rule '{}' (expected first or rolloff)", s)),
+        }
+    }
+}
+
+// A bot and the spec it was made from
+type ChosenBot = (String, Box<dyn PlayerController>);
+
+// A turn as it was played, with what it takes to take it back
+struct PlayedTurn {
+    player_id: PlayerId,
+    dice_value: u8,
+    piece_idx: Option<usize>, // None when there was nothing to move
+    from: PieceLocation,
+    captured: Vec<Piece>,
+    bonus_turn: bool, // Whether the turn was an extra turn
+    stats: GameStats, // As they were before the turn
+}
 
 pub struct Game {
     players: Vec<Player>,
+    controllers: Vec<Option<Box<dyn PlayerController>>>, // None for human players
+    controller_specs: Vec<Option<String>>, // What each bot was made from, for saving
     board: Board,
     dice: Dice,
     current_player_idx: usize,
     ui: UI,
     player_colors: HashMap<PlayerId, Color>,
     game_over: bool,
+    winner: Option<PlayerId>, // None if the game was stopped early
+    resigned: Vec<bool>,
+    stats: GameStats,
+    start_rule: StartRule,
+    first_player_idx: usize,
+    show_win_probabilities: bool,
+    win_log: Option<File>, // One CSV line of win probabilities per turn
+    history: Vec<TurnRecord>,
+    start_position: Board, // Where the game record begins
+    moves: Vec<RecordedMove>, // Everything played since, for the game record
+    record_path: Option<String>, // Write the game record here when the game ends
+    undo_allowed: bool,
+    undo_rerolls: bool, // Whether a move taken back is played with a new roll
+    undo_stack: Vec<PlayedTurn>,
+    redo_stack: Vec<PlayedTurn>, // Most recently taken back on top
+    pending_roll: Option<u8>, // The roll of a move taken back, to be played again
+    resumed: bool, // Loaded from a save, so already started
+    autosave: bool, // Save to `save::autosave_path()` after every turn
 }
 
 impl Game {
-    pub fn new() -> Self {
+    // Set up a game by asking at the terminal, unless the player quits
+    pub fn new() -> Result<Self, Quit> {
     let mut ui = UI::new();
-    let player_count = ui.get_player_count();
+    let player_count = ui.get_player_count()?;
+        
+        let mut names = Vec::new();
+        let mut controllers = Vec::new();
+        let mut controller_specs = Vec::new();
         
-        let colors = vec![
-            Color::Red, 
-            Color::Green, 
-            Color::Blue, 
-            Color::Yellow
-        ];
+        for i in 0..player_count {
+            let name = ui.get_player_name(i)?;
+            let controller = Self::ask_for_controller(&mut ui, i, &name)?;
+            controller_specs.push(controller.as_ref().map(|(spec, _)| spec.clone()));
+            controllers.push(controller.map(|(_, bot)| bot));
+            names.push(name);
+        }
         
+        Ok(Self::with_players(names, controllers, controller_specs, Self::new_dice(), ui))
+    }
+    
+    // A game between bots only, with seeded dice and no terminal I/O
+    pub fn headless(bots: Vec<Box<dyn PlayerController>>, seed: u64) -> Self {
+        let player_count = bots.len();
+        let names = bots.iter().map(|bot| bot.name().to_string()).collect();
+        let controllers = bots.into_iter().map(Some).collect();
+        // Headless games are never saved
+        let game = Self::with_players(names, controllers, vec![None; player_count], Dice::seeded(6, seed), UI::silent());
+        Game {
+            show_win_probabilities: false,
+            undo_allowed: false,
+            autosave: false,
+            ..game
+        }
+    }
+    
+    // A new game between these players as an interactive game starts it;
+    // the constructors change whatever differs from that
+    fn with_players(
+        names: Vec<String>,
+        controllers: Vec<Option<Box<dyn PlayerController>>>,
+        controller_specs: Vec<Option<String>>,
+        dice: Dice,
+        ui: UI,
+    ) -> Self {
+        let player_count = names.len();
         let mut players = Vec::new();
         let mut player_colors = HashMap::new();
         
-        for i in 0..player_count {
-            let name = ui.get_player_name(i);
-            let color = colors[i % colors.len()];
+        for (i, name) in names.into_iter().enumerate() {
+            let color = PLAYER_COLORS[i % PLAYER_COLORS.len()];
             player_colors.insert(i, color);
             players.push(Player::new(i, name, color));
         }
         
         Game {
             players,
+            controllers,
+            controller_specs,
             board: Board::new(player_count),
-            dice: Dice::default(),
+            dice,
             current_player_idx: 0,
             ui,
             player_colors,
             game_over: false,
+            winner: None,
+            resigned: vec![false; player_count],
+            stats: GameStats::default(),
+            start_rule: StartRule::FirstSeat,
+            first_player_idx: 0,
+            show_win_probabilities: true,
+            win_log: None,
+            history: Vec::new(),
+            start_position: Board::new(player_count),
+            moves: Vec::new(),
+            record_path: None,
+            undo_allowed: true,
+            undo_rerolls: false,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            pending_roll: None,
+            resumed: false,
+            autosave: true,
+        }
+    }
+    
+    // Carry on a game saved with `save`
+    pub fn load(path: &str) -> Result<Self, String> {
+        let saved = SavedGame::load(Path::new(path))?;
+        let unsealed = saved.seal(seal::league_key().as_deref())? == Seal::Missing;
+        let game = Self::from_saved(saved, seal::league_key().as_deref())?;
+        if unsealed {
+            game.ui.display_error(&format!("'{}' has no league checksum, so it may have been edited.", path));
         }
+        Ok(game)
     }
     
-    pub fn start(&mut self) {
+    // If an earlier game was left unfinished, offer to carry it on
+    pub fn offer_autosave() -> Option<Self> {
+        let path = save::autosave_path();
+        if !path.exists() {
+            return None;
+        }
+        
+        let mut ui = UI::new();
+        let saved = match SavedGame::load(&path) {
+            Ok(saved) => saved,
+            Err(err) => {
+                ui.display_error(&format!("Ignoring the autosaved game: {}", err));
+                return None;
+            }
+        };
+        
+        if saved.seal(seal::league_key().as_deref()) == Ok(Seal::Missing) {
+            ui.display_error("The autosaved game has no league checksum, so it may have been edited.");
+        }
+        
+        let names: Vec<&str> = saved.players.iter().map(|player| player.name.as_str()).collect();
+        let question = format!(
+            "An unfinished game between {} was left after {} turns. Resume it? (y/N): ",
+            names.join(", "),
+            saved.stats.turns
+        );
+        if !ui.confirm(&question) {
+            return None;
+        }
+        
+        match Self::from_saved(saved, seal::league_key().as_deref()) {
+            Ok(game) => Some(game),
+            Err(err) => {
+                ui.display_error(&format!("Cannot resume the autosaved game: {}", err));
+                None
+            }
+        }
+    }
+    
+    // Dice for a new game. In a league game they roll from the seed signed
+    // with the league key, so the seed written to saves does not give the
+    // rolls away.
+    fn new_dice() -> Dice {
+        let seed = rand::random();
+        Dice::keyed(6, seed, seal::league_key().map(|key| seal::dice_seed(&key, seed)))
+    }
+    
+    fn from_saved(saved: SavedGame, key: Option<&str>) -> Result<Self, String> {
+        let secret = match (saved.dice_keyed, key) {
+            (false, _) => None,
+            (true, Some(key)) => Some(seal::dice_seed(key, saved.dice_seed)),
+            (true, None) => return Err("this is a league game: set LUDO_LEAGUE_KEY to roll its dice".to_string()),
+        };
+        
+        let mut controllers = Vec::new();
+        for player in &saved.players {
+            controllers.push(match &player.controller {
+                Some(spec) => Some(bot::from_spec(spec, &player.name)?),
+                None => None,
+            });
+        }
+        
+        let resigned = saved.players.iter().map(|player| player.resigned).collect();
+        let (names, controller_specs) = saved.players.into_iter().map(|player| (player.name, player.controller)).unzip();
+        let dice = Dice::resume(saved.dice_sides, saved.dice_seed, secret, saved.dice_rolls);
+        let game = Self::with_players(names, controllers, controller_specs, dice, UI::new());
+        Ok(Game {
+            current_player_idx: saved.board.to_move(),
+            start_position: saved.board.clone(),
+            board: saved.board,
+            resigned,
+            stats: saved.stats,
+            first_player_idx: saved.first_player,
+            pending_roll: saved.pending_roll,
+            resumed: true,
+            ..game
+        })
+    }
+    
+    // The game as it stands at the start of the current turn
+    fn to_saved(&self) -> SavedGame {
+        SavedGame {
+            players: self
+                .players
+                .iter()
+                .zip(&self.controller_specs)
+                .map(|(player, spec)| SavedPlayer {
+                    name: player.name.clone(),
+                    controller: spec.clone(),
+                    resigned: self.resigned[player.id],
+                })
+                .collect(),
+            board: self.board.clone(),
+            dice_sides: self.dice.sides(),
+            dice_seed: self.dice.seed(),
+            dice_keyed: self.dice.is_keyed(),
+            dice_rolls: self.dice.rolls(),
+            pending_roll: self.pending_roll,
+            first_player: self.first_player_idx,
+            stats: self.stats,
+            checksum: None,
+        }
+    }
+    
+    // The bot a player chose and the spec it was made from, or None for a human
+    fn ask_for_controller(ui: &mut UI, player_id: PlayerId, name: &str) -> Result<Option<ChosenBot>, Quit> {
+        loop {
+            let spec = match ui.get_bot_personality(player_id)? {
+                Some(spec) => spec,
+                None => return Ok(None),
+            };
+            
+            match bot::from_spec(&spec, name) {
+                Ok(bot) => return Ok(Some((spec, bot))),
+                Err(err) => ui.display_error(&err),
+            }
+        }
+    }
+    
+    pub fn stats(&self) -> GameStats {
+        self.stats
+    }
+    
+    pub fn set_start_rule(&mut self, start_rule: StartRule) {
+        self.start_rule = start_rule;
+    }
+    
+    // Log every turn's win probabilities to a CSV file for charting
+    pub fn set_win_log(&mut self, path: &str) -> Result<(), String> {
+        let mut file = File::create(path).map_err(|err| format!("cannot create '{}': {}", path, err))?;
+        let columns: Vec<String> = self.players.iter().map(|player| format!("p{}", player.id)).collect();
+        writeln!(file, "turn,to_move,{}", columns.join(","))
+            .map_err(|err| format!("cannot write '{}': {}", path, err))?;
+        self.win_log = Some(file);
+        Ok(())
+    }
+    
+    // Whether human players may take back moves, and whether a move taken
+    // back is played again with the same roll or a new one
+    pub fn set_undo(&mut self, allowed: bool, rerolls: bool) {
+        self.undo_allowed = allowed;
+        self.undo_rerolls = rerolls;
+    }
+    
+    // Write the game record to a file once the game ends
+    pub fn set_record_path(&mut self, path: &str) {
+        self.record_path = Some(path.to_string());
+    }
+    
+    // The game so far as a game record. A resumed game's record starts
+    // where it was resumed.
+    pub fn record(&self) -> GameRecord {
+        // Seats that resigned before it was resumed have no entry saying so
+        let resigned_before = (0..self.players.len())
+            .filter(|&player_id| self.resigned[player_id] && !self.moves.contains(&RecordedMove::Resign { player_id }))
+            .collect();
+        GameRecord {
+            date: Some(record::today()),
+            // The seed of keyed dice is no use without the league key
+            seed: Some(self.dice.seed()).filter(|_| !self.dice.is_keyed()),
+            players: self.players.iter().map(|player| player.name.clone()).collect(),
+            start: self.start_position.clone(),
+            resigned: resigned_before,
+            moves: self.moves.clone(),
+            result: self.winner,
+            variations: Vec::new(),
+            checksum: None,
+        }
+    }
+    
+    // Seat of the player who took the first turn
+    pub fn first_player(&self) -> PlayerId {
+        self.first_player_idx
+    }
+    
+    // Hand back the bots of a headless game, in seat order, to play again
+    pub fn into_bots(self) -> Vec<Box<dyn PlayerController>> {
+        self.controllers.into_iter().flatten().collect()
+    }
+    
+    // Everyone rolls once; the highest roll starts and tied players roll again
+    fn roll_off(&mut self) -> PlayerId {
+        let mut contenders: Vec<PlayerId> = self.players.iter().map(|player| player.id).collect();
+        
+        while contenders.len() > 1 {
+            let rolls: Vec<(PlayerId, u8)> = contenders.iter().map(|&id| (id, self.dice.roll())).collect();
+            for &(id, value) in &rolls {
+                self.ui.display_roll_off_roll(&self.players[id], value);
+            }
+            
+            let highest = rolls.iter().map(|&(_, value)| value).max().unwrap();
+            contenders = rolls.iter().filter(|&&(_, value)| value == highest).map(|&(id, _)| id).collect();
+        }
+        
+        contenders[0]
+    }
+    
+    // Play until someone wins and return the winner, or None if the game
+    // was stopped first
+    pub fn start(&mut self) -> Option<PlayerId> {
         self.ui.display_welcome();
         
+        if self.resumed {
+            self.ui.display_message(&format!("Resuming the saved game after {} turns.", self.stats.turns));
+        } else {
+            if self.start_rule == StartRule::RollOff {
+                self.current_player_idx = self.roll_off();
+                self.ui.display_first_player(&self.players[self.current_player_idx]);
+            }
+            self.first_player_idx = self.current_player_idx;
+            self.board.set_turn(self.current_player_idx, false);
+        }
+        self.start_position = self.board.clone();
+        
         while !self.game_over {
             self.play_turn();
+            if self.autosave && !self.game_over {
+                if let Err(err) = self.to_saved().save(&save::autosave_path()) {
+                    self.ui.display_error(&format!("Autosave failed, carrying on without it: {}", err));
+                    self.autosave = false;
+                }
+            }
         }
         
-        self.ui.display_game_over(&self.players[self.current_player_idx]);
+        if let Some(path) = &self.record_path {
+            match self.record().save(Path::new(path)) {
+                Ok(()) => self.ui.display_message(&format!("Game record written to {}.", path)),
+                Err(err) => self.ui.display_error(&err),
+            }
+        }
+        
+        let winner = match self.winner {
+            Some(winner) => winner,
+            None => {
+                self.ui.display_message("Quitting game. Goodbye!");
+                return None;
+            }
+        };
+        
+        // Nothing left to resume
+        if self.autosave {
+            let _ = fs::remove_file(save::autosave_path());
+        }
+        
+        self.ui.display_game_over(&self.players[winner]);
+        
+        if self.ui.confirm("Analyse the game for blunders and luck? (y/N): ") {
+            self.ui.display_message("Analysing...");
+            let report = analysis::analyse(&self.history, self.players.len(), analysis::DEFAULT_ROLLOUTS);
+            self.ui.display_analysis(&report, &self.players);
+        }
+        
+        Some(winner)
     }
     
     fn play_turn(&mut self) {
-        let current_player = &self.players[self.current_player_idx];
+        let player_id = self.current_player_idx;
+        let human = self.controllers[player_id].is_none();
         
-        self.ui.display_board(&self.board, &self.player_colors);
-        self.ui.display_player_turn(current_player);
+        let win_probabilities = if self.show_win_probabilities || self.win_log.is_some() {
+            let resigned: Vec<PlayerId> = (0..self.players.len()).filter(|&id| self.resigned[id]).collect();
+            let mut rng = rand::thread_rng();
+            let probabilities = rollout::win_probabilities(&self.board, player_id, &resigned, DEFAULT_ROLLOUTS, &mut rng);
+            if let Some(log) = &mut self.win_log {
+                let columns: Vec<String> = probabilities.iter().map(|p| format!("{:.3}", p)).collect();
+                // Losing the log is not worth stopping the game for
+                let _ = writeln!(log, "{},{},{}", self.stats.turns + 1, player_id, columns.join(","));
+            }
+            Some(probabilities)
+        } else {
+            None
+        };
         
-        // Roll dice
-        self.ui.prompt_for_dice_roll();
-        let dice_value = self.dice.roll();
-        self.ui.display_dice_roll(dice_value);
+        let shown = win_probabilities.as_deref().filter(|_| self.show_win_probabilities);
+        self.ui.display_board(&self.board, &self.player_colors, player_id, shown);
+        self.ui.display_player_turn(&self.players[player_id]);
         
-        // Get valid pieces that can move
-        let mut valid_pieces = Vec::new();
+        // The game as it stands before the roll, in case a human player
+        // quits and wants to save it
+        let turn_start = if human { Some(self.to_saved()) } else { None };
         
-        for piece_idx in 0..TOTAL_PIECES_PER_PLAYER {
-            let location = self.board.get_piece_location(current_player.id, piece_idx);
-            
-            match location {
-                crate::board::PieceLocation::Yard if dice_value == 6 => {
-                    valid_pieces.push(piece_idx);
-                }
-                crate::board::PieceLocation::MainTrack(_) | crate::board::PieceLocation::HomeTrack(_) => {
-                    valid_pieces.push(piece_idx);
+        // Roll dice (bots don't need to press Enter)
+        if let Some(turn_start) = &turn_start {
+            loop {
+                match self.ui.prompt_for_dice_roll(self.undo_allowed) {
+                    RollChoice::Roll => break,
+                    RollChoice::Undo => {
+                        if self.undo() {
+                            return;
+                        }
+                    }
+                    RollChoice::Redo => {
+                        if self.redo() {
+                            return;
+                        }
+                    }
+                    RollChoice::Save => {
+                        if let Some(path) = self.ui.get_save_path() {
+                            self.save_to(turn_start, &path);
+                        }
+                    }
+                    RollChoice::Quit => {
+                        if self.quit(player_id, turn_start) {
+                            return;
+                        }
+                    }
                 }
-                _ => {}
             }
         }
+        let dice_value = match self.pending_roll.take() {
+            Some(dice_value) => dice_value,
+            None => self.dice.roll(),
+        };
+        self.ui.display_dice_roll(dice_value);
+        
+        // Get valid pieces that can move
+        let valid_pieces = self.board.legal_moves(player_id, dice_value as usize);
         
         // If there are no valid pieces to move, skip turn
         if valid_pieces.is_empty() {
             self.ui.display_no_valid_moves();
-            self.next_player();
+            self.play_new(player_id, dice_value, None);
             return;
         }
         
-        // Let player choose a piece to move
-        let chosen_piece = self.ui.choose_piece(current_player.id, &valid_pieces);
+        // Let player (or their bot) choose a piece to move
+        let chosen_piece = match &mut self.controllers[player_id] {
+            Some(bot) => {
+                let piece_idx = bot.choose_piece(&self.board, player_id, dice_value, &valid_pieces);
+                self.ui.display_bot_choice(bot.name(), piece_idx);
+                piece_idx
+            }
+            None => loop {
+                match self.ui.choose_piece(&valid_pieces) {
+                    PieceChoice::Piece(piece_idx) => break piece_idx,
+                    PieceChoice::Hint => {
+                        let hint = advisor::hint(&self.board, player_id, dice_value, &valid_pieces);
+                        self.ui.display_hint(&hint);
+                    }
+                    PieceChoice::Quit => {
+                        if self.quit(player_id, turn_start.as_ref().expect("human turns keep the turn start")) {
+                            return;
+                        }
+                    }
+                }
+            },
+        };
+        
+        self.play_new(player_id, dice_value, Some(chosen_piece));
+    }
+    
+    // Play a turn that is not a redo, so what was taken back is gone for good
+    fn play_new(&mut self, player_id: PlayerId, dice_value: u8, chosen: Option<usize>) {
+        self.redo_stack.clear();
+        self.play(player_id, dice_value, chosen);
+    }
+    
+    // Carry out a roll and the piece chosen for it, or None when nothing
+    // could move
+    fn play(&mut self, player_id: PlayerId, dice_value: u8, chosen: Option<usize>) {
+        let mut played = PlayedTurn {
+            player_id,
+            dice_value,
+            piece_idx: chosen,
+            from: PieceLocation::Yard,
+            captured: Vec::new(),
+            bonus_turn: self.board.bonus_turn(),
+            stats: self.stats,
+        };
+        
+        self.stats.turns += 1;
+        if dice_value == 6 {
+            self.stats.sixes += 1;
+        }
+        self.history.push(TurnRecord {
+            player_id,
+            dice_value,
+            board_before: self.board.clone(),
+            chosen,
+        });
+        
+        let chosen_piece = match chosen {
+            Some(piece_idx) => piece_idx,
+            None => {
+                self.moves.push(RecordedMove::Pass { player_id, dice_value });
+                self.remember(played);
+                self.next_player();
+                return;
+            }
+        };
+        self.moves.push(RecordedMove::Move { player_id, dice_value, piece_idx: chosen_piece });
         
         // Move the piece
-        let result = self.board.move_piece(current_player.id, chosen_piece, dice_value as usize);
+        played.from = self.board.get_piece_location(player_id, chosen_piece);
+        let before = self.board.clone();
+        let result = self.board.move_piece(player_id, chosen_piece, dice_value as usize);
+        for victim in (0..self.players.len()).filter(|&other| other != player_id) {
+            for victim_idx in 0..TOTAL_PIECES_PER_PLAYER {
+                if !before.is_in_yard(victim, victim_idx) && self.board.is_in_yard(victim, victim_idx) {
+                    played.captured.push(Piece::new(victim, victim_idx));
+                }
+            }
+        }
+        self.remember(played);
         
         match result {
             MoveResult::Moved => {
-                self.ui.display_move_result(current_player.id, chosen_piece, "moved successfully");
+                self.ui.display_move_result(player_id, chosen_piece, "moved successfully");
             }
             MoveResult::Captured => {
-                self.ui.display_move_result(current_player.id, chosen_piece, "captured an opponent's piece");
+                self.stats.captures += 1;
+                self.ui.display_move_result(player_id, chosen_piece, "captured an opponent's piece");
                 // Player gets another turn after capturing
+                self.board.set_turn(player_id, true);
                 return;
             }
             MoveResult::Finished => {
-                self.ui.display_move_result(current_player.id, chosen_piece, "reached the finish");
+                self.ui.display_move_result(player_id, chosen_piece, "reached the finish");
                 
                 // Check if player has won
-                if self.board.has_won(current_player.id) {
+                if self.board.has_won(player_id) {
+                    self.winner = Some(player_id);
                     self.game_over = true;
                     return;
                 }
             }
             MoveResult::InvalidMove => {
-                self.ui.display_move_result(current_player.id, chosen_piece, "couldn't move (invalid move)");
+                self.ui.display_move_result(player_id, chosen_piece, "couldn't move (invalid move)");
             }
         }
         
         // If player rolled a 6, they get another turn
         if dice_value == 6 && !self.game_over {
             self.ui.display_extra_turn();
+            self.board.set_turn(player_id, true);
             return;
         }
         
         self.next_player();
     }
     
+    fn remember(&mut self, played: PlayedTurn) {
+        if self.undo_allowed {
+            self.undo_stack.push(played);
+        }
+    }
+    
+    // Whether a turn was a move chosen by a human player, the kind of turn
+    // undo and redo step between
+    fn is_human_move(&self, played: &PlayedTurn) -> bool {
+        played.piece_idx.is_some() && self.controllers[played.player_id].is_none()
+    }
+    
+    // Take back every turn since the last move a human player made, and
+    // that move itself. Returns true if anything was taken back.
+    fn undo(&mut self) -> bool {
+        let last = match self.undo_stack.iter().rposition(|played| self.is_human_move(played)) {
+            Some(last) => last,
+            None => {
+                self.ui.display_message("Nothing to undo.");
+                return false;
+            }
+        };
+        while self.undo_stack.len() > last {
+            let played = self.take_back();
+            self.redo_stack.push(played);
+        }
+        
+        let played = self.redo_stack.last().expect("a move was just taken back");
+        let name = &self.players[played.player_id].name;
+        if self.undo_rerolls {
+            self.pending_roll = None;
+            self.ui.display_message(&format!("Took back {}'s move. They roll again.", name));
+        } else {
+            self.pending_roll = Some(played.dice_value);
+            self.ui.display_message(&format!("Took back {}'s move. They play their {} again.", name, played.dice_value));
+        }
+        true
+    }
+    
+    // Play again the move taken back last, and the turns that followed it
+    // up to the next human move. Returns true if anything was replayed.
+    fn redo(&mut self) -> bool {
+        let mut played = match self.redo_stack.pop() {
+            Some(played) => played,
+            None => {
+                self.ui.display_message("Nothing to redo.");
+                return false;
+            }
+        };
+        self.pending_roll = None;
+        
+        loop {
+            let name = &self.players[played.player_id].name;
+            self.ui.display_message(&format!("Replaying {}'s roll of {}.", name, played.dice_value));
+            self.play(played.player_id, played.dice_value, played.piece_idx);
+            
+            match self.redo_stack.last() {
+                Some(next) if !self.is_human_move(next) => played = self.redo_stack.pop().expect("just looked at it"),
+                _ => return true,
+            }
+        }
+    }
+    
+    // Undo the last turn on the undo stack: the piece goes back, captured
+    // pieces return to the square it landed on, and the turn, counters and
+    // record are as they were before it
+    fn take_back(&mut self) -> PlayedTurn {
+        let played = self.undo_stack.pop().expect("there is a turn to take back");
+        if let Some(piece_idx) = played.piece_idx {
+            let landed = self.board.get_piece_location(played.player_id, piece_idx);
+            self.board
+                .set_piece_location(played.player_id, piece_idx, played.from)
+                .expect("a piece can always go back where it came from");
+            for piece in &played.captured {
+                self.board
+                    .set_piece_location(piece.player_id, piece.index, landed)
+                    .expect("captured pieces can always go back");
+            }
+        }
+        
+        self.current_player_idx = played.player_id;
+        self.board.set_turn(played.player_id, played.bonus_turn);
+        self.stats = played.stats;
+        self.history.pop();
+        self.moves.pop();
+        played
+    }
+    
+    // A human player typed quit: offer to save, resign or abandon. Returns
+    // true if their turn is over, because the game stopped or they resigned.
+    fn quit(&mut self, player_id: PlayerId, turn_start: &SavedGame) -> bool {
+        match self.ui.choose_quit_action(&self.players[player_id]) {
+            QuitChoice::Save => {
+                let saved = match self.ui.get_save_path() {
+                    Some(path) => self.save_to(turn_start, &path),
+                    None => false,
+                };
+                self.game_over = saved;
+                saved
+            }
+            QuitChoice::Resign => {
+                self.resign(player_id);
+                true
+            }
+            QuitChoice::Abandon => {
+                // A closed terminal is not a decision to throw the game
+                // away, so the autosave is only removed when asked
+                if self.autosave && !self.ui.input_closed() {
+                    let _ = fs::remove_file(save::autosave_path());
+                }
+                self.autosave = false;
+                self.game_over = true;
+                true
+            }
+            QuitChoice::KeepPlaying => false,
+        }
+    }
+    
+    fn save_to(&self, saved: &SavedGame, path: &str) -> bool {
+        match saved.save(Path::new(path)) {
+            Ok(()) => {
+                self.ui.display_message(&format!("Game saved. Resume it with `ludo load {}`.", path));
+                true
+            }
+            Err(err) => {
+                self.ui.display_error(&err);
+                false
+            }
+        }
+    }
+    
+    // Take a player out of the game. Their pieces leave the board and their
+    // turns are skipped; the last player left wins.
+    fn resign(&mut self, player_id: PlayerId) {
+        self.resigned[player_id] = true;
+        self.moves.push(RecordedMove::Resign { player_id });
+        // Moves before a resignation cannot be taken back
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.pending_roll = None;
+        for piece_idx in 0..TOTAL_PIECES_PER_PLAYER {
+            self.board
+                .set_piece_location(player_id, piece_idx, PieceLocation::Yard)
+                .expect("the yard always has room");
+        }
+        self.ui.display_message(&format!("{} resigns.", self.players[player_id].name));
+        
+        let remaining: Vec<PlayerId> = (0..self.players.len()).filter(|&id| !self.resigned[id]).collect();
+        if let [last] = remaining[..] {
+            self.current_player_idx = last;
+            self.winner = Some(last);
+            self.game_over = true;
+        } else {
+            self.next_player();
+        }
+    }
+    
     fn next_player(&mut self) {
-        self.current_player_idx = (self.current_player_idx + 1) % self.players.len();
+        loop {
+            self.current_player_idx = (self.current_player_idx + 1) % self.players.len();
+            if !self.resigned[self.current_player_idx] {
+                break;
+            }
+        }
+        self.board.set_turn(self.current_player_idx, false);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    
+    // A headless game between balanced bots
+    pub(crate) fn bot_game(players: usize, seed: u64) -> Game {
+        let bots = (0..players).map(|_| bot::from_spec("balanced", "Bot").unwrap()).collect();
+        Game::headless(bots, seed)
+    }
+    
+    // The four-player save from tests/saves, where P0 has resigned
+    fn four_player_save() -> SavedGame {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/saves/v2-four-players.save");
+        SavedGame::parse(&fs::read_to_string(path).unwrap()).unwrap()
+    }
+    
+    // Take back whole bot games turn by turn: every position, counter and
+    // captured piece must come back exactly as it was
+    #[test]
+    fn taking_back_turns_restores_every_position() {
+        let mut captures = 0;
+        for seed in 0..20 {
+            let players = 2 + seed as usize % 3;
+            let mut game = bot_game(players, seed);
+            game.set_undo(true, false);
+            game.start();
+            captures += game.undo_stack.iter().filter(|played| !played.captured.is_empty()).count();
+            
+            while !game.undo_stack.is_empty() {
+                let before = game.history.last().unwrap().board_before.clone();
+                let moves = game.moves.len();
+                let played = game.take_back();
+                assert_eq!(game.board.to_notation(), before.to_notation());
+                assert_eq!(game.board.hash(), before.hash());
+                assert_eq!(game.stats.turns, game.history.len());
+                assert_eq!(game.moves.len(), moves - 1);
+                assert_eq!(game.current_player_idx, played.player_id);
+            }
+            assert_eq!(game.board.to_notation(), game.start_position.to_notation());
+            assert_eq!(game.stats.captures, 0);
+        }
+        assert!(captures > 0);
+    }
+    
+    // Replay the first round of the roll-off on fresh dice: a single
+    // highest roll starts, tied players roll again among themselves
+    #[test]
+    fn roll_off_rerolls_ties_and_the_winner_moves_first() {
+        let mut ties = 0;
+        for seed in 0..40 {
+            let players = 2 + seed as usize % 3;
+            let mut game = bot_game(players, seed);
+            let winner = game.roll_off();
+            
+            let mut dice = Dice::seeded(6, seed);
+            let first_round: Vec<u8> = (0..players).map(|_| dice.roll()).collect();
+            let highest = *first_round.iter().max().unwrap();
+            let tied: Vec<PlayerId> = (0..players).filter(|&id| first_round[id] == highest).collect();
+            if tied.len() == 1 {
+                assert_eq!(winner, tied[0]);
+                assert_eq!(game.dice.rolls(), players as u64);
+            } else {
+                assert!(tied.contains(&winner), "seed {}: {} was not in the tie", seed, winner);
+                assert!(game.dice.rolls() >= (players + tied.len()) as u64);
+                ties += 1;
+            }
+            
+            let mut game = bot_game(players, seed);
+            game.set_start_rule(StartRule::RollOff);
+            game.start();
+            assert_eq!(game.first_player(), winner);
+            assert_eq!(game.history[0].player_id, winner);
+        }
+        assert!(ties > 0);
+    }
+    
+    // Play a human turn the way play_turn does once the player has picked
+    // a piece: the roll kept by undo first, then the last legal move
+    fn play_human_turn(game: &mut Game) -> u8 {
+        let player_id = game.current_player_idx;
+        let dice_value = match game.pending_roll.take() {
+            Some(dice_value) => dice_value,
+            None => game.dice.roll(),
+        };
+        let chosen = game.board.legal_moves(player_id, dice_value as usize).last().copied();
+        game.play_new(player_id, dice_value, chosen);
+        dice_value
     }
-}
\ No newline at end of file
+    
+    // P0 plays against a bot until it has moved and the bot has answered
+    fn game_with_a_human_move(seed: u64) -> Game {
+        let mut game = bot_game(2, seed);
+        game.controllers[0] = None;
+        game.set_undo(true, false);
+        game.board.set_turn(0, false);
+        loop {
+            assert!(!game.game_over);
+            if game.current_player_idx == 0 {
+                let last_human = game.undo_stack.iter().rposition(|played| game.is_human_move(played));
+                if game.stats.turns >= 12 && last_human.is_some_and(|last| last + 1 < game.undo_stack.len()) {
+                    return game;
+                }
+                play_human_turn(&mut game);
+            } else {
+                game.play_turn();
+            }
+        }
+    }
+    
+    // Undo takes back the bot's answers with the human move and keeps the
+    // roll; redo plays all of it again
+    #[test]
+    fn undo_and_redo_step_between_human_moves() {
+        for seed in 0..10 {
+            let mut game = game_with_a_human_move(seed);
+            let after = (game.board.to_notation(), game.moves.clone(), game.stats.turns);
+            let last = game.undo_stack.iter().rposition(|played| game.is_human_move(played)).unwrap();
+            let before = game.history[last].board_before.to_notation();
+            let roll = game.history[last].dice_value;
+            let taken = game.undo_stack.len() - last;
+            
+            assert!(game.undo());
+            assert_eq!(game.board.to_notation(), before);
+            assert_eq!(game.current_player_idx, 0);
+            assert_eq!(game.redo_stack.len(), taken);
+            assert!(taken >= 2);
+            assert_eq!(game.pending_roll, Some(roll));
+            
+            assert!(game.redo());
+            assert_eq!((game.board.to_notation(), game.moves.clone(), game.stats.turns), after);
+            assert_eq!(game.pending_roll, None);
+            assert!(game.redo_stack.is_empty());
+            
+            // A new move after undo plays the same roll and drops the redo stack
+            assert!(game.undo());
+            let rolls = game.dice.rolls();
+            assert_eq!(play_human_turn(&mut game), roll);
+            assert_eq!(game.dice.rolls(), rolls);
+            assert!(game.redo_stack.is_empty());
+            assert!(!game.redo());
+            
+            game.set_undo(true, true);
+            assert!(game.undo());
+            assert_eq!(game.pending_roll, None);
+        }
+    }
+    
+    // The dice of a league game roll from the seed signed with the league
+    // key, so the seed in the save is not enough to know the rolls to come
+    #[test]
+    fn league_games_roll_from_the_keyed_seed() {
+        let saved = |keyed| {
+            let mut saved = four_player_save();
+            saved.dice_keyed = keyed;
+            saved
+        };
+        let rolls = |game: &mut Game| -> Vec<u8> { (0..20).map(|_| game.dice.roll()).collect() };
+        
+        assert!(Game::from_saved(saved(true), None).is_err());
+        let mut plain = Game::from_saved(saved(false), Some("league")).unwrap();
+        let mut league = Game::from_saved(saved(true), Some("league")).unwrap();
+        let mut other = Game::from_saved(saved(true), Some("another league")).unwrap();
+        assert!(!plain.dice.is_keyed());
+        assert!(league.dice.is_keyed());
+        
+        let resaved = league.to_saved();
+        assert!(resaved.dice_keyed);
+        assert_eq!(resaved.dice_seed, saved(false).dice_seed);
+        assert_eq!(league.record().seed, None);
+        assert_eq!(plain.record().seed, Some(saved(false).dice_seed));
+        
+        let league_rolls = rolls(&mut league);
+        assert_ne!(league_rolls, rolls(&mut plain));
+        assert_ne!(league_rolls, rolls(&mut other));
+        let mut again = Game::from_saved(resaved, Some("league")).unwrap();
+        assert_eq!(rolls(&mut again), league_rolls);
+    }
+    
+    // A resumed game whose record starts after a seat resigned
+    #[test]
+    fn resumed_games_record_earlier_resignations() {
+        let mut game = Game::from_saved(four_player_save(), None).unwrap();
+        game.controllers = (0..4).map(|_| Some(bot::from_spec("balanced", "Bot").unwrap())).collect();
+        game.ui = UI::silent();
+        game.autosave = false;
+        game.show_win_probabilities = false;
+        let winner = game.start();
+        assert!(winner.is_some_and(|winner| winner != 0));
+        
+        let record = game.record();
+        assert_eq!(record.resigned, [0]);
+        let text = record.to_text();
+        assert!(text.contains("[Resigned \"P0\"]\n"), "{}", text);
+        let parsed = GameRecord::parse(&text).unwrap();
+        assert_eq!(parsed.resigned, [0]);
+        assert_eq!(parsed.moves, record.moves);
+        assert_eq!(parsed.result, winner);
+        assert_eq!(parsed.to_text(), text);
+        
+        // Without the header the replay gives P0 a turn
+        assert!(GameRecord::parse(&text.replace("[Resigned \"P0\"]\n", "")).is_err());
+    }
+}
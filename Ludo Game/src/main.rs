@@ -1,12 +1,128 @@
+mod advisor;
+mod analysis;
+mod bench;
 mod board;
+mod bot;
+mod cli;
 mod dice;
+mod endgame;
+mod engine;
+mod eval;
 mod game;
+mod perft;
 mod player;
+mod record;
+mod replay;
+mod rollout;
+mod save;
+mod seal;
+mod seats;
+mod sim;
+mod tournament;
+mod tune;
 mod ui;
 
-use game::Game;
+use cli::Options;
+use game::{Game, StartRule};
+use record::GameRecord;
+use seal::Seal;
+use std::env;
+use std::path::Path;
+use std::process;
+use ui::Quit;
 
 fn main() {
-    let mut game = Game::new();
+    let args: Vec<String> = env::args().skip(1).collect();
+    
+    let result = match args.first().map(String::as_str) {
+        None => play(&Options::parse(&[]).unwrap()),
+        Some(arg) if arg.starts_with("--") => Options::parse(&args).and_then(|options| play(&options)),
+        Some("bench") => Options::parse(&args[1..]).and_then(|options| bench::run(&options)),
+        Some("load") => match args.get(1) {
+            Some(path) => Options::parse(&args[2..]).and_then(|options| load(path, &options)),
+            None => Err("usage: ludo load <file>".to_string()),
+        },
+        Some("perft") => Options::parse(&args[1..]).and_then(|options| perft::run(&options)),
+        Some("replay") => match args.get(1) {
+            Some(path) => replay::run(path),
+            None => Err("usage: ludo replay <record>".to_string()),
+        },
+        Some("seats") => Options::parse(&args[1..]).and_then(|options| seats::run(&options)),
+        Some("simulate") => Options::parse(&args[1..]).and_then(|options| sim::run(&options)),
+        Some("tablebase") => Options::parse(&args[1..]).and_then(|options| endgame::run(&options)),
+        Some("tournament") => Options::parse(&args[1..]).and_then(|options| tournament::run(&options)),
+        Some("tune") => Options::parse(&args[1..]).and_then(|options| tune::run(&options)),
+        Some("verify") => match args.get(1) {
+            Some(path) => verify(path),
+            None => Err("usage: ludo verify <record>".to_string()),
+        },
+        Some(other) => Err(format!("unknown command '{}' (expected: bench, load, perft, replay, seats, simulate, tablebase, tournament, tune, verify)", other)),
+    };
+    
+    if let Err(err) = result {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
+
+// Interactive game at the terminal
+fn play(options: &Options) -> Result<(), String> {
+    let start_rule = options.get_or("start", StartRule::FirstSeat)?;
+    
+    let game = match Game::offer_autosave() {
+        Some(game) => game,
+        None => match Game::new() {
+            Ok(mut game) => {
+                game.set_start_rule(start_rule);
+                game
+            }
+            Err(Quit) => {
+                println!("Quitting game. Goodbye!");
+                return Ok(());
+            }
+        },
+    };
+    run_interactive(game, options)
+}
+
+// Carry on a saved game
+fn load(path: &str, options: &Options) -> Result<(), String> {
+    run_interactive(Game::load(path)?, options)
+}
+
+fn run_interactive(mut game: Game, options: &Options) -> Result<(), String> {
+    game.set_undo(options.get_or("undo", true)?, options.get_or("undo-rerolls", false)?);
+    if let Some(path) = options.get_str("winprob-log") {
+        game.set_win_log(path)?;
+    }
+    if let Some(path) = options.get_str("record") {
+        game.set_record_path(path);
+    }
     game.start();
-}
\ No newline at end of file
+    Ok(())
+}
+
+// Check a game record against the rules
+fn verify(path: &str) -> Result<(), String> {
+    let record = GameRecord::load(Path::new(path))?;
+    let outcome = match record.result {
+        Some(winner) => format!("won by {}", record.players[winner]),
+        None => "unfinished".to_string(),
+    };
+    let checksum = match record.seal(seal::league_key().as_deref())? {
+        Seal::Valid => "the league checksum matches",
+        Seal::Missing => "it has no league checksum, so it may have been edited",
+        Seal::Unchecked => "the checksum was not checked (set LUDO_LEAGUE_KEY)",
+    };
+    println!(
+        "{}: {}, {} after {} entries and {} variation{}; every move follows the rules and {}",
+        path,
+        record.players.join(" v "),
+        outcome,
+        record.moves.len(),
+        record.variations.len(),
+        if record.variations.len() == 1 { "" } else { "s" },
+        checksum
+    );
+    Ok(())
+}
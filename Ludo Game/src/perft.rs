@@ -0,0 +1,305 @@
+use crate::board::{Board, MoveResult};
+use crate::cli::Options;
+use crate::player::PlayerId;
+use crate::rollout;
+use std::time::Instant;
+
+// `ludo perft`: count the move tree below a position, depth by depth, to
+// check that rule changes do not alter which moves exist
+pub fn run(options: &Options) -> Result<(), String> {
+    let depth = options.get_or("depth", 4usize)?;
+    let divide = options.get_or("divide", false)?;
+    let (board, to_move) = match options.get_str("position") {
+        Some(text) => {
+            let board = Board::from_notation(text)?;
+            let to_move = board.to_move();
+            (board, to_move)
+        },
+        None => {
+            let players = options.get_or("players", 2usize)?;
+            if !(2..=4).contains(&players) {
+                return Err("--players must be between 2 and 4".to_string());
+            }
+            (Board::new(players), 0)
+        }
+    };
+
+    println!("Position: {}", board.to_notation());
+
+    if divide && depth > 0 {
+        // Subtree sizes below every roll and move at the root
+        for dice_value in 1..=6u8 {
+            let legal_moves = board.legal_moves(to_move, dice_value as usize);
+            if legal_moves.is_empty() {
+                let next = (to_move + 1) % board.player_count();
+                println!("  R{} pass: {}", dice_value, perft(&board, next, depth - 1));
+            }
+            for piece_idx in legal_moves {
+                let (after, next) = play(&board, to_move, piece_idx, dice_value);
+                println!("  R{} P{}: {}", dice_value, piece_idx, perft(&after, next, depth - 1));
+            }
+        }
+    }
+
+    for d in 1..=depth {
+        let started = Instant::now();
+        let nodes = perft(&board, to_move, d);
+        let elapsed = started.elapsed().as_secs_f64();
+        println!(
+            "perft({}) = {:>14}  ({:.2}s, {:.1} M nodes/s)",
+            d,
+            nodes,
+            elapsed,
+            nodes as f64 / elapsed.max(1e-9) / 1e6
+        );
+    }
+
+    Ok(())
+}
+
+// Number of lines of exactly `depth` turns from a position, over all six
+// dice outcomes at every turn. A roll with no legal move is a pass and still
+// counts as a turn. A won game has no further turns, so lines in which
+// someone wins before the last turn are not counted.
+pub fn perft(board: &Board, to_move: PlayerId, depth: usize) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    if (0..board.player_count()).any(|player_id| board.has_won(player_id)) {
+        return 0;
+    }
+
+    let mut nodes = 0;
+    let mut passes = 0;
+
+    for dice_value in 1..=6u8 {
+        let legal_moves = board.legal_moves(to_move, dice_value as usize);
+        if legal_moves.is_empty() {
+            passes += 1;
+        } else if depth == 1 {
+            nodes += legal_moves.len() as u64;
+        } else {
+            for piece_idx in legal_moves {
+                let (after, next) = play(board, to_move, piece_idx, dice_value);
+                nodes += perft(&after, next, depth - 1);
+            }
+        }
+    }
+
+    // Every passing roll leads to the same position
+    if passes > 0 {
+        nodes += passes * perft(board, (to_move + 1) % board.player_count(), depth - 1);
+    }
+
+    nodes
+}
+
+fn play(board: &Board, to_move: PlayerId, piece_idx: usize, dice_value: u8) -> (Board, PlayerId) {
+    let mut after = board.clone();
+    let result = after.move_piece(to_move, piece_idx, dice_value as usize);
+    debug_assert_ne!(result, MoveResult::InvalidMove);
+    let next = rollout::next_to_move(&after, to_move, &result, dice_value, &[]);
+    (after, next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{PieceLocation, TOTAL_PIECES_PER_PLAYER};
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::{Rng, SeedableRng};
+
+    const SQUARES: usize = 52;
+    const HOME_ENTRY: usize = 46; // Squares walked before turning into the home track
+    const HOME_SQUARES: usize = 6;
+
+    // The rules written out again from scratch, as plainly as possible and
+    // without sharing any code with `Board`: pieces sit on absolute squares
+    // and remember how far they have walked from their start
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Spot {
+        Yard,
+        Track { square: usize, walked: usize },
+        Home(usize),
+        Done,
+    }
+
+    #[derive(Debug, Clone)]
+    struct Reference {
+        spots: Vec<[Spot; TOTAL_PIECES_PER_PLAYER]>,
+    }
+
+    impl Reference {
+        fn from_board(board: &Board) -> Self {
+            let spots = (0..board.player_count())
+                .map(|player_id| {
+                    let mut pieces = [Spot::Yard; TOTAL_PIECES_PER_PLAYER];
+                    for (piece_idx, spot) in pieces.iter_mut().enumerate() {
+                        *spot = match board.get_piece_location(player_id, piece_idx) {
+                            PieceLocation::Yard => Spot::Yard,
+                            PieceLocation::MainTrack(square) => Spot::Track {
+                                square,
+                                walked: (square + SQUARES - start(board.player_count(), player_id)) % SQUARES,
+                            },
+                            PieceLocation::HomeTrack(home) => Spot::Home(home),
+                            PieceLocation::Finished => Spot::Done,
+                        };
+                    }
+                    pieces
+                })
+                .collect();
+            Reference { spots }
+        }
+
+        fn players(&self) -> usize {
+            self.spots.len()
+        }
+
+        fn won(&self, player: usize) -> bool {
+            self.spots[player].iter().all(|&spot| spot == Spot::Done)
+        }
+
+        // The position after a move and whether it captured, or None if
+        // the move is not allowed
+        fn play(&self, player: usize, piece: usize, roll: usize) -> Option<(Reference, bool)> {
+            let target = match self.spots[player][piece] {
+                Spot::Yard if roll == 6 => Spot::Track { square: start(self.players(), player), walked: 0 },
+                Spot::Yard => return None,
+                Spot::Track { square, walked } => {
+                    let walked = walked + roll;
+                    if walked == SQUARES {
+                        // Exactly one full lap: back on the start square
+                        // rather than into the home track
+                        Spot::Track { square: start(self.players(), player), walked: 0 }
+                    } else if walked > HOME_ENTRY {
+                        Spot::Home(walked - HOME_ENTRY - 1)
+                    } else {
+                        Spot::Track { square: (square + roll) % SQUARES, walked }
+                    }
+                }
+                Spot::Home(home) if home + roll == HOME_SQUARES => Spot::Done,
+                Spot::Home(home) if home + roll < HOME_SQUARES => Spot::Home(home + roll),
+                Spot::Home(_) | Spot::Done => return None,
+            };
+
+            // One piece per home track square
+            if let Spot::Home(_) = target {
+                if self.spots[player].contains(&target) {
+                    return None;
+                }
+            }
+
+            let mut next = self.clone();
+            next.spots[player][piece] = target;
+
+            // Landing on a square sends every opponent there home, but
+            // coming out of the yard never does
+            let mut captured = false;
+            if let (Spot::Track { square, .. }, false) = (target, self.spots[player][piece] == Spot::Yard) {
+                for other in (0..self.players()).filter(|&other| other != player) {
+                    for spot in next.spots[other].iter_mut() {
+                        if matches!(*spot, Spot::Track { square: s, .. } if s == square) {
+                            *spot = Spot::Yard;
+                            captured = true;
+                        }
+                    }
+                }
+            }
+
+            Some((next, captured))
+        }
+    }
+
+    fn start(players: usize, player: usize) -> usize {
+        player * (SQUARES / players)
+    }
+
+    fn reference_perft(position: &Reference, to_move: usize, depth: usize) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        if (0..position.players()).any(|player| position.won(player)) {
+            return 0;
+        }
+
+        let mut nodes = 0;
+        for roll in 1..=6 {
+            let mut moved = false;
+            for piece in 0..TOTAL_PIECES_PER_PLAYER {
+                if let Some((next, captured)) = position.play(to_move, piece, roll) {
+                    moved = true;
+                    let next_to_move = if captured || roll == 6 { to_move } else { (to_move + 1) % position.players() };
+                    nodes += reference_perft(&next, next_to_move, depth - 1);
+                }
+            }
+            if !moved {
+                nodes += reference_perft(position, (to_move + 1) % position.players(), depth - 1);
+            }
+        }
+        nodes
+    }
+
+    fn check(board: &Board, to_move: PlayerId, depth: usize) {
+        assert_eq!(
+            perft(board, to_move, depth),
+            reference_perft(&Reference::from_board(board), to_move, depth),
+            "perft({}) differs from the reference at {} with player {} to move",
+            depth,
+            board.to_notation(),
+            to_move
+        );
+    }
+
+    #[test]
+    fn opening_counts() {
+        // Five rolls pass, a six can bring out any of the four pieces
+        assert_eq!(perft(&Board::new(2), 0, 1), 9);
+        assert_eq!(perft(&Board::new(2), 0, 4), 7821);
+        for players in 2..=4 {
+            for depth in 1..=5 {
+                check(&Board::new(players), 0, depth);
+            }
+        }
+    }
+
+    // Positions from random games cover captures, stacks, the home track
+    // and wins, which the opening never reaches within a few turns
+    #[test]
+    fn matches_reference_from_random_positions() {
+        let mut rng = StdRng::seed_from_u64(40);
+
+        for game in 0..30 {
+            let players = 2 + game % 3;
+            let mut board = Board::new(players);
+            let mut to_move = 0;
+
+            for turn in 0.. {
+                if turn % 25 == 0 {
+                    check(&board, to_move, 3);
+                }
+                if (0..players).any(|player_id| board.has_won(player_id)) {
+                    break;
+                }
+
+                let dice_value = rng.gen_range(1..=6u8);
+                match board.legal_moves(to_move, dice_value as usize).choose(&mut rng) {
+                    Some(&piece_idx) => (board, to_move) = play(&board, to_move, piece_idx, dice_value),
+                    None => to_move = (to_move + 1) % players,
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn endgame_positions_match_reference() {
+        for text in [
+            "h5fff/t50fff 0 - classic",
+            "t46h4ff/t20t20h0f 1 - classic",
+            "t12yh2f/t14t14yy/t26t20t20h5/t45ffy 2 - classic",
+        ] {
+            let board = Board::from_notation(text).unwrap();
+            check(&board, board.to_move(), 4);
+        }
+    }
+}
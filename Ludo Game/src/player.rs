@@ -2,6 +2,14 @@ use colored::Color;
 
 pub type PlayerId = usize;
 
+// Seat colours, in seat order
+pub const PLAYER_COLORS: [Color; 4] = [Color::Red, Color::Green, Color::Blue, Color::Yellow];
+pub const PLAYER_COLOR_NAMES: [&str; 4] = ["Red", "Green", "Blue", "Yellow"];
+
+pub fn color_name(player_id: PlayerId) -> &'static str {
+    PLAYER_COLOR_NAMES[player_id % PLAYER_COLOR_NAMES.len()]
+}
+
 #[derive(Debug)]
 pub struct Player {
     pub id: PlayerId,
@@ -15,7 +23,7 @@ impl Player {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Piece {
     pub player_id: PlayerId,
     pub index: usize,
@@ -0,0 +1,638 @@
+use crate::board::{Board, MoveResult, PieceLocation, RULESET_ID, TOTAL_PIECES_PER_PLAYER};
+use crate::player::PlayerId;
+use crate::seal::{self, Seal};
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Everything needed to look back at one turn of a finished game
+#[derive(Debug, Clone)]
+pub struct TurnRecord {
+    pub player_id: PlayerId,
+    pub dice_value: u8,
+    pub board_before: Board,
+    pub chosen: Option<usize>, // None when there was nothing to move
+}
+
+// One numbered entry of a game record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordedMove {
+    Move { player_id: PlayerId, dice_value: u8, piece_idx: usize },
+    Pass { player_id: PlayerId, dice_value: u8 },
+    Resign { player_id: PlayerId },
+}
+
+impl RecordedMove {
+    pub fn player_id(&self) -> PlayerId {
+        match *self {
+            RecordedMove::Move { player_id, .. } | RecordedMove::Pass { player_id, .. } | RecordedMove::Resign { player_id } => player_id,
+        }
+    }
+}
+
+// A whole game as portable text, in the spirit of PGN: `[Key "value"]`
+// headers followed by numbered entries such as
+//
+//     1. R6 P0:2 out
+//     2. R4 P0:2 0→4
+//     3. R3 P1 pass
+//     4. R5 P1:0 17→22 x P0:2
+//     5. P1 resigns
+//
+// Moves give the roll, the player and piece, and where the piece went:
+// main track squares are numbers, `h<n>` is a home track square and `f`
+// the finish. Pieces sent back to the yard follow `x`. A variation, a line
+// tried instead of an entry, follows that entry in parentheses:
+//
+//     6. R2 P0:2 4→6
+//       (6. R2 P0:0 15→17 x P1:1
+//        7. R6 P0:0 17→23)
+#[derive(Debug, Clone)]
+pub struct GameRecord {
+    pub date: Option<String>, // YYYY-MM-DD
+    pub seed: Option<u64>,
+    pub players: Vec<String>,
+    pub start: Board, // Including the player to move first
+    pub resigned: Vec<PlayerId>, // Seats that had resigned before `start`
+    pub moves: Vec<RecordedMove>,
+    pub result: Option<PlayerId>, // The winner, None if the game was not finished
+    pub variations: Vec<Variation>,
+    pub checksum: Option<String>, // As read from the file, see `seal`
+}
+
+// Another way the game could have gone, played instead of one of the
+// entries of the main line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variation {
+    pub at: usize, // Index in `moves` of the entry it replaces
+    pub moves: Vec<RecordedMove>,
+}
+
+impl GameRecord {
+    // Records whose checksum shows they were edited are not loaded
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("cannot read '{}': {}", path.display(), err))?;
+        let record = Self::parse(&text).map_err(|err| format!("'{}': {}", path.display(), err))?;
+        record.seal(seal::league_key().as_deref()).map_err(|err| format!("'{}': {}", path.display(), err))?;
+        Ok(record)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_text()).map_err(|err| format!("cannot write '{}': {}", path.display(), err))
+    }
+
+    // Signed with the league key if there is one. Otherwise a checksum the
+    // record was read with is kept, as it does not cover variations.
+    pub fn to_text(&self) -> String {
+        self.to_text_with_key(seal::league_key().as_deref())
+    }
+
+    fn to_text_with_key(&self, key: Option<&str>) -> String {
+        let mut lines = self.header_lines();
+        let checksum = match key {
+            Some(key) => Some(seal::sign(key, &self.signed_text())),
+            None => self.checksum.clone(),
+        };
+        if let Some(checksum) = checksum {
+            lines.push(header("Checksum", &checksum));
+        }
+        lines.push(String::new());
+
+        for (number, (annotation, _)) in self.positions().iter().enumerate() {
+            lines.push(format!("{}. {}", number + 1, annotation));
+            for variation in self.variations.iter().filter(|variation| variation.at == number) {
+                let entries: Vec<String> = self
+                    .variation_positions(variation)
+                    .into_iter()
+                    .enumerate()
+                    .map(|(offset, (annotation, _))| format!("{}. {}", number + offset + 1, annotation))
+                    .collect();
+                lines.push(format!("  ({})", entries.join("\n   ")));
+            }
+        }
+
+        let mut text = lines.join("\n");
+        text.push('\n');
+        text
+    }
+
+    fn header_lines(&self) -> Vec<String> {
+        let mut lines = vec![header("Variant", RULESET_ID)];
+        if let Some(date) = &self.date {
+            lines.push(header("Date", date));
+        }
+        if let Some(seed) = self.seed {
+            lines.push(header("Seed", &seed.to_string()));
+        }
+        for (player_id, name) in self.players.iter().enumerate() {
+            lines.push(header(&format!("Player{}", player_id), name));
+        }
+        lines.push(header("Position", &self.start.to_notation()));
+        if !self.resigned.is_empty() {
+            let seats: Vec<String> = self.resigned.iter().map(|player_id| format!("P{}", player_id)).collect();
+            lines.push(header("Resigned", &seats.join(" ")));
+        }
+        lines.push(header("Result", &self.result.map_or("*".to_string(), |winner| format!("P{}", winner))));
+        lines
+    }
+
+    // What the checksum covers: the headers and every roll and move of the
+    // main line, but not variations, which can be added later by anyone
+    fn signed_text(&self) -> String {
+        let mut lines = self.header_lines();
+        for (number, (annotation, _)) in self.positions().iter().enumerate() {
+            lines.push(format!("{}. {}", number + 1, annotation));
+        }
+        lines.join("\n")
+    }
+
+    // Check the record's checksum. Fails if the record was changed since
+    // it was signed, or signed with another key.
+    pub fn seal(&self, key: Option<&str>) -> Result<Seal, String> {
+        seal::check(key, &self.signed_text(), self.checksum.as_deref())
+    }
+
+    // Every entry of the main line in record notation, with the board after it
+    pub fn positions(&self) -> Vec<(String, Board)> {
+        Replay::new(&self.start, &self.resigned).play_out(&self.moves)
+    }
+
+    // The same for the entries of a variation
+    pub fn variation_positions(&self, variation: &Variation) -> Vec<(String, Board)> {
+        self.replay_to(variation.at).play_out(&variation.moves)
+    }
+
+    // The main line played up to, but not including, entry `at`
+    pub fn replay_to(&self, at: usize) -> Replay {
+        let mut replay = Replay::new(&self.start, &self.resigned);
+        replay.play_out(&self.moves[..at]);
+        replay
+    }
+
+    // Read a record and play every entry against the board, so a record
+    // whose moves, squares, captures or result do not follow the rules is
+    // rejected
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut headers = Vec::new();
+        let mut body = Vec::new();
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            match line.strip_prefix('[') {
+                Some(tag) if body.is_empty() => headers.push(parse_header(tag)?),
+                _ => body.push(line),
+            }
+        }
+        let header = |key: &str| headers.iter().find(|(name, _)| name == key).map(|(_, value)| value.as_str());
+
+        let variant = header("Variant").ok_or("missing the Variant header")?;
+        if variant != RULESET_ID {
+            return Err(format!("recorded under the '{}' rules, but this build plays '{}'", variant, RULESET_ID));
+        }
+        let players: Vec<String> = (0..)
+            .map_while(|player_id| header(&format!("Player{}", player_id)))
+            .map(str::to_string)
+            .collect();
+        let start = match header("Position") {
+            Some(notation) => Board::from_notation(notation)?,
+            None if (2..=4).contains(&players.len()) => Board::new(players.len()),
+            None => return Err("the record needs a Player header for each of 2 to 4 players".to_string()),
+        };
+        if start.player_count() != players.len() {
+            return Err(format!("the position is for {} players, but {} are named", start.player_count(), players.len()));
+        }
+        // A resumed game's record can start after some seats resigned
+        let mut resigned = Vec::new();
+        for seat in header("Resigned").unwrap_or_default().split_whitespace() {
+            match seat.strip_prefix('P').and_then(|seat| seat.parse::<PlayerId>().ok()) {
+                Some(player_id) if player_id < players.len() && !resigned.contains(&player_id) => resigned.push(player_id),
+                _ => return Err(format!("invalid resigned seat '{}' (expected seats such as P0 P2)", seat)),
+            }
+        }
+        if resigned.contains(&start.to_move()) {
+            return Err(format!("P{} is to move, but had resigned", start.to_move()));
+        }
+        if resigned.len() + 1 >= players.len() {
+            return Err("at most one seat is left, so the game is already over".to_string());
+        }
+        let seed = match header("Seed") {
+            Some(seed) => Some(seed.parse().map_err(|_| format!("invalid seed '{}'", seed))?),
+            None => None,
+        };
+        let result = match header("Result") {
+            None | Some("*") => None,
+            Some(result) => match result.strip_prefix('P').and_then(|seat| seat.parse::<PlayerId>().ok()) {
+                Some(winner) if winner < players.len() => Some(winner),
+                _ => return Err(format!("invalid result '{}' (expected * or a seat such as P0)", result)),
+            },
+        };
+
+        // Entries start with their number, so the tokens in between belong
+        // to the entry before. Variations are collected with the index of
+        // the main line entry they follow.
+        let body = body.join("\n").replace('(', " ( ").replace(')', " ) ");
+        let mut entries: Vec<Vec<&str>> = Vec::new();
+        let mut variation_entries: Vec<(usize, Vec<Vec<&str>>)> = Vec::new();
+        let mut open: Option<(usize, Vec<Vec<&str>>)> = None;
+        for token in body.split_whitespace() {
+            match token {
+                "(" if open.is_some() => return Err("variations inside variations are not supported".to_string()),
+                "(" if entries.is_empty() => return Err("a variation must follow the entry it replaces".to_string()),
+                "(" => open = Some((entries.len() - 1, Vec::new())),
+                ")" => match open.take() {
+                    Some((_, variation)) if variation.is_empty() => return Err("empty variation".to_string()),
+                    Some(variation) => variation_entries.push(variation),
+                    None => return Err("')' without a matching '('".to_string()),
+                },
+                _ => {
+                    let (line, first) = match &mut open {
+                        Some((at, variation)) => (variation, *at + 1),
+                        None => (&mut entries, 1),
+                    };
+                    if let Some(number) = token.strip_suffix('.') {
+                        if number.parse() != Ok(first + line.len()) {
+                            return Err(format!("expected entry {}, found '{}'", first + line.len(), token));
+                        }
+                        line.push(Vec::new());
+                    } else {
+                        line.last_mut().ok_or_else(|| format!("'{}' comes before a numbered entry", token))?.push(token);
+                    }
+                }
+            }
+        }
+        if open.is_some() {
+            return Err("a variation is missing its ')'".to_string());
+        }
+
+        // Every main line position a variation branches from
+        let mut replay = Replay::new(&start, &resigned);
+        let mut branch_points = Vec::new();
+        let mut moves = Vec::new();
+        for (number, tokens) in entries.iter().enumerate() {
+            branch_points.push(replay.clone());
+            moves.push(play_entry(&mut replay, number + 1, tokens)?);
+        }
+
+        let mut variations = Vec::new();
+        for (at, variation) in &variation_entries {
+            let mut branch = branch_points[*at].clone();
+            let moves = variation
+                .iter()
+                .enumerate()
+                .map(|(offset, tokens)| play_entry(&mut branch, at + offset + 1, tokens))
+                .collect::<Result<_, _>>()
+                .map_err(|err| format!("in the variation on entry {}: {}", at + 1, err))?;
+            variations.push(Variation { at: *at, moves });
+        }
+
+        if result.is_some() && result != replay.winner {
+            return Err(format!("the result is P{}, but the moves do not end with that win", result.unwrap_or_default()));
+        }
+        if let (None, Some(winner)) = (result, replay.winner) {
+            return Err(format!("the moves end with P{} winning, but no result is given", winner));
+        }
+
+        Ok(GameRecord {
+            date: header("Date").map(str::to_string),
+            seed,
+            players,
+            start,
+            resigned,
+            moves,
+            result,
+            variations,
+            checksum: header("Checksum").map(str::to_string),
+        })
+    }
+}
+
+// Read one numbered entry and play it, checking that what is written
+// matches what happens on the board
+fn play_entry(replay: &mut Replay, number: usize, tokens: &[&str]) -> Result<RecordedMove, String> {
+    let written = tokens.join(" ").replace("->", "→");
+    let entry = parse_entry(tokens).ok_or_else(|| format!("entry {} '{}' is not a move, pass or resignation", number, written))?;
+    let annotation = replay.apply(entry).map_err(|err| format!("entry {} '{}': {}", number, written, err))?;
+    if annotation != written {
+        return Err(format!("entry {} '{}' does not match the position, which gives '{}'", number, written, annotation));
+    }
+    Ok(entry)
+}
+
+// The board as the entries of a record are played out, keeping track of
+// resignations and the winner the way `Game` does
+#[derive(Debug, Clone)]
+pub struct Replay {
+    board: Board,
+    resigned: Vec<bool>,
+    winner: Option<PlayerId>,
+}
+
+impl Replay {
+    pub fn new(start: &Board, resigned: &[PlayerId]) -> Self {
+        Replay {
+            board: start.clone(),
+            resigned: (0..start.player_count()).map(|player_id| resigned.contains(&player_id)).collect(),
+            winner: None,
+        }
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn winner(&self) -> Option<PlayerId> {
+        self.winner
+    }
+
+    // Play entries that are known to be valid, returning each in record
+    // notation with the board after it
+    fn play_out(&mut self, moves: &[RecordedMove]) -> Vec<(String, Board)> {
+        moves
+            .iter()
+            .map(|&entry| {
+                let annotation = self.apply(entry).expect("recorded games replay cleanly");
+                (annotation, self.board.clone())
+            })
+            .collect()
+    }
+
+    // Play an entry and describe it in record notation
+    pub fn apply(&mut self, entry: RecordedMove) -> Result<String, String> {
+        if self.winner.is_some() {
+            return Err("the game is already over".to_string());
+        }
+        let player_id = entry.player_id();
+        if player_id != self.board.to_move() {
+            return Err(format!("it is P{}'s turn", self.board.to_move()));
+        }
+
+        match entry {
+            RecordedMove::Move { dice_value, piece_idx, .. } => {
+                if !(1..=6).contains(&dice_value) || piece_idx >= TOTAL_PIECES_PER_PLAYER {
+                    return Err("no such roll or piece".to_string());
+                }
+                let before = self.board.clone();
+                let result = self.board.move_piece(player_id, piece_idx, dice_value as usize);
+                if result == MoveResult::InvalidMove {
+                    return Err(format!("P{}:{} cannot move {}", player_id, piece_idx, dice_value));
+                }
+
+                let mut annotation = format!("R{} P{}:{} ", dice_value, player_id, piece_idx);
+                match before.get_piece_location(player_id, piece_idx) {
+                    PieceLocation::Yard => annotation.push_str("out"),
+                    from => annotation.push_str(&format!(
+                        "{}→{}",
+                        square(from),
+                        square(self.board.get_piece_location(player_id, piece_idx))
+                    )),
+                }
+                for victim in (0..self.board.player_count()).filter(|&other| other != player_id) {
+                    for victim_idx in 0..TOTAL_PIECES_PER_PLAYER {
+                        if !before.is_in_yard(victim, victim_idx) && self.board.is_in_yard(victim, victim_idx) {
+                            annotation.push_str(&format!(" x P{}:{}", victim, victim_idx));
+                        }
+                    }
+                }
+
+                if result == MoveResult::Finished && self.board.has_won(player_id) {
+                    self.winner = Some(player_id);
+                } else if result == MoveResult::Captured || dice_value == 6 {
+                    self.board.set_turn(player_id, true);
+                } else {
+                    self.next_player();
+                }
+                Ok(annotation)
+            }
+            RecordedMove::Pass { dice_value, .. } => {
+                if !(1..=6).contains(&dice_value) {
+                    return Err("no such roll".to_string());
+                }
+                if !self.board.legal_moves(player_id, dice_value as usize).is_empty() {
+                    return Err(format!("P{} has a move with {}", player_id, dice_value));
+                }
+                self.next_player();
+                Ok(format!("R{} P{} pass", dice_value, player_id))
+            }
+            RecordedMove::Resign { .. } => {
+                self.resigned[player_id] = true;
+                for piece_idx in 0..TOTAL_PIECES_PER_PLAYER {
+                    self.board
+                        .set_piece_location(player_id, piece_idx, PieceLocation::Yard)
+                        .expect("the yard always has room");
+                }
+                let remaining: Vec<PlayerId> = (0..self.resigned.len()).filter(|&id| !self.resigned[id]).collect();
+                if let [last] = remaining[..] {
+                    self.winner = Some(last);
+                } else {
+                    self.next_player();
+                }
+                Ok(format!("P{} resigns", player_id))
+            }
+        }
+    }
+
+    fn next_player(&mut self) {
+        let mut next = self.board.to_move();
+        loop {
+            next = (next + 1) % self.resigned.len();
+            if !self.resigned[next] {
+                break;
+            }
+        }
+        self.board.set_turn(next, false);
+    }
+}
+
+// The move or resignation an entry's leading tokens describe. The rest of
+// the entry is checked by comparing it with the replayed annotation.
+fn parse_entry(tokens: &[&str]) -> Option<RecordedMove> {
+    let roll = |token: &str| token.strip_prefix('R')?.parse::<u8>().ok();
+    let player = |token: &str| token.strip_prefix('P')?.parse::<PlayerId>().ok();
+
+    match tokens {
+        [player_token, "resigns"] => Some(RecordedMove::Resign { player_id: player(player_token)? }),
+        [roll_token, player_token, "pass"] => Some(RecordedMove::Pass {
+            player_id: player(player_token)?,
+            dice_value: roll(roll_token)?,
+        }),
+        [roll_token, piece_token, ..] => {
+            let (player_token, piece_idx) = piece_token.split_once(':')?;
+            Some(RecordedMove::Move {
+                player_id: player(player_token)?,
+                dice_value: roll(roll_token)?,
+                piece_idx: piece_idx.parse().ok()?,
+            })
+        }
+        _ => None,
+    }
+}
+
+fn square(location: PieceLocation) -> String {
+    match location {
+        PieceLocation::Yard => "y".to_string(),
+        PieceLocation::MainTrack(pos) => pos.to_string(),
+        PieceLocation::HomeTrack(home_pos) => format!("h{}", home_pos),
+        PieceLocation::Finished => "f".to_string(),
+    }
+}
+
+fn header(key: &str, value: &str) -> String {
+    format!("[{} \"{}\"]", key, value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// `Key "value"]`, the rest of a header line after its opening bracket
+fn parse_header(tag: &str) -> Result<(String, String), String> {
+    let invalid = || format!("invalid header '[{}'", tag);
+    let (key, quoted) = tag.strip_suffix(']').and_then(|tag| tag.split_once(' ')).ok_or_else(invalid)?;
+    let quoted = quoted.trim().strip_prefix('"').and_then(|quoted| quoted.strip_suffix('"')).ok_or_else(invalid)?;
+
+    let mut value = String::new();
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        value.push(if c == '\\' { chars.next().ok_or_else(invalid)? } else { c });
+    }
+    Ok((key.to_string(), value))
+}
+
+// Today's date in UTC as YYYY-MM-DD
+pub fn today() -> String {
+    let days = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs() / 86_400) as i64;
+
+    // Days since 1970-01-01 to a civil date, counting in 400-year eras
+    // that start on 1 March so leap days fall at the end of a year
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::tests::bot_game;
+
+    const CAPTURE: &str = "[Variant \"classic\"]
+[Player0 \"Ann\"]
+[Player1 \"Ben \\\"the Bold\\\"\"]
+[Position \"t26yyy/t30yyy 0 - classic\"]
+[Result \"*\"]
+
+1. R4 P0:0 26->30 x P1:0
+2. R3 P0:0 30→33
+3. R5 P1 pass
+";
+
+    #[test]
+    fn bot_games_round_trip() {
+        for seed in 0..6 {
+            let players = 2 + seed as usize % 3;
+            let mut game = bot_game(players, seed);
+            let winner = game.start();
+
+            let record = game.record();
+            let text = record.to_text();
+            let parsed = GameRecord::parse(&text).unwrap();
+            assert_eq!(parsed.moves, record.moves);
+            assert_eq!(parsed.result, winner);
+            assert_eq!(parsed.seed, Some(seed));
+            assert_eq!(parsed.to_text(), text);
+        }
+    }
+
+    #[test]
+    fn reads_hand_written_records() {
+        let record = GameRecord::parse(CAPTURE).unwrap();
+        assert_eq!(record.players, ["Ann", "Ben \"the Bold\""]);
+        assert_eq!(
+            record.moves,
+            [
+                RecordedMove::Move { player_id: 0, dice_value: 4, piece_idx: 0 },
+                RecordedMove::Move { player_id: 0, dice_value: 3, piece_idx: 0 },
+                RecordedMove::Pass { player_id: 1, dice_value: 5 },
+            ]
+        );
+        assert!(record.to_text().contains("1. R4 P0:0 26→30 x P1:0\n"));
+        assert_eq!(GameRecord::parse(&record.to_text()).unwrap().players, record.players);
+    }
+
+    #[test]
+    fn rejects_records_that_break_the_rules() {
+        for (from, to) in [
+            ("26->30 x P1:0", "26->30"),           // Capture left out
+            ("26->30", "26->31"),                  // Wrong square
+            ("1. R4", "1. R5"),                    // Roll does not fit the squares
+            ("2. R3 P0:0", "2. R3 P1:0"),          // The capture earned P0 another turn
+            ("3. R5 P1 pass", "3. R6 P1 pass"),    // A six brings a piece out
+            ("3. R5", "4. R5"),                    // Entries out of order
+            ("\"classic\"]", "\"house\"]"),        // Other rules
+            ("[Result \"*\"]", "[Result \"P0\"]"), // Nobody has won
+            ("3. R5 P1 pass", "3. R5 P1 pass 4. R2 P0:0 33→35 5. R1 P1 pass 6. R1 P0:3 out"),
+        ] {
+            assert!(CAPTURE.contains(from));
+            let text = CAPTURE.replacen(from, to, 1);
+            assert!(GameRecord::parse(&text).is_err(), "accepted:\n{}", text);
+        }
+    }
+
+    #[test]
+    fn variations_round_trip_and_are_checked() {
+        let text = CAPTURE.replace("30→33\n", "30→33\n  (2. R6 P0:1 out 3. R1 P0:0 30->31)\n");
+        let record = GameRecord::parse(&text).unwrap();
+        assert_eq!(
+            record.variations,
+            [Variation {
+                at: 1,
+                moves: vec![
+                    RecordedMove::Move { player_id: 0, dice_value: 6, piece_idx: 1 },
+                    RecordedMove::Move { player_id: 0, dice_value: 1, piece_idx: 0 },
+                ],
+            }]
+        );
+        assert!(record.to_text().contains("2. R3 P0:0 30→33\n  (2. R6 P0:1 out\n   3. R1 P0:0 30→31)\n3. R5 P1 pass"));
+        assert_eq!(GameRecord::parse(&record.to_text()).unwrap().variations, record.variations);
+
+        for (from, to) in [
+            ("3. R1 P0:0 30->31", "3. R1 P0:0 30->32"),         // Wrong square
+            ("3. R1", "4. R1"),                                 // Numbered as if after entry 3
+            ("(2. R6", "(2. R6 (2. R5"),                        // Nested
+            ("30->31)", "30->31"),                              // Never closed
+            ("(2. R6 P0:1 out 3. R1 P0:0 30->31)", "()"),       // Empty
+            ("3. R1 P0:0 30->31", "3. R1 P0:0 30->31 4. R6 P0:2 out"), // P1 is to move
+        ] {
+            let broken = text.replacen(from, to, 1);
+            assert!(GameRecord::parse(&broken).is_err(), "accepted:\n{}", broken);
+        }
+    }
+
+    #[test]
+    fn league_checksums_catch_edited_records() {
+        let unsigned = GameRecord::parse(CAPTURE).unwrap();
+        assert_eq!(unsigned.seal(Some("league")), Ok(Seal::Missing));
+        assert_eq!(unsigned.seal(None), Ok(Seal::Unchecked));
+
+        let signed = unsigned.to_text_with_key(Some("league"));
+        let record = GameRecord::parse(&signed).unwrap();
+        assert_eq!(record.seal(Some("league")), Ok(Seal::Valid));
+        assert!(record.seal(Some("another league")).is_err());
+
+        // Variations can be added to a signed record without a key
+        let mut annotated = record.clone();
+        annotated.variations.push(Variation { at: 1, moves: vec![RecordedMove::Move { player_id: 0, dice_value: 6, piece_idx: 1 }] });
+        let annotated = GameRecord::parse(&annotated.to_text_with_key(None)).unwrap();
+        assert_eq!(annotated.seal(Some("league")), Ok(Seal::Valid));
+
+        for (from, to) in [
+            ("R3 P0:0 30→33", "R2 P0:0 30→32"), // A different roll
+            ("R5 P1 pass", "R5 P1 pass\n4. R4 P0:0 33→37"),
+            ("Ann", "Ana"),
+        ] {
+            let edited = GameRecord::parse(&signed.replace(from, to)).unwrap();
+            assert!(edited.seal(Some("league")).is_err(), "accepted {} -> {}", from, to);
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use crate::advisor;
+use crate::board::Board;
+use crate::bot;
+use crate::player::{Player, PlayerId, PLAYER_COLORS};
+use crate::record::{GameRecord, RecordedMove, Replay, Variation};
+use crate::seal::{self, Seal};
+use crate::ui::{BranchCommand, PieceChoice, ReplayCommand, UI};
+use colored::Color;
+use rand::Rng;
+use std::collections::HashMap;
+use std::path::Path;
+
+// `ludo replay <record>`: step through a recorded game position by
+// position, showing the roll and move that led to each. Any position can
+// be branched from to try something else; kept branches are written back
+// to the record as variations.
+pub fn run(path: &str) -> Result<(), String> {
+    let mut record = GameRecord::load(Path::new(path))?;
+    let players: Vec<Player> = record
+        .players
+        .iter()
+        .enumerate()
+        .map(|(player_id, name)| Player::new(player_id, name.clone(), PLAYER_COLORS[player_id % PLAYER_COLORS.len()]))
+        .collect();
+    let player_colors: HashMap<_, _> = players.iter().map(|player| (player.id, player.color)).collect();
+
+    let mut ui = UI::new();
+    if record.seal(seal::league_key().as_deref())? == Seal::Missing {
+        ui.display_error(&format!("'{}' has no league checksum, so it may have been edited.", path));
+    }
+    let mut line = Line::main(&record);
+    let mut step = 0;
+    loop {
+        let (board, annotation) = match step {
+            0 => (&line.start, None),
+            _ => {
+                let (annotation, board) = &line.positions[step - 1];
+                let player_id = line.moves[step - 1].player_id();
+                (board, Some((&players[player_id], annotation.as_str())))
+            }
+        };
+        ui.display_board(board, &player_colors, board.to_move(), None);
+        ui.display_replay_entry(line.first + step, line.first + line.moves.len(), annotation);
+        if line.variation.is_none() {
+            let alternatives: Vec<(usize, String)> = record
+                .variations
+                .iter()
+                .enumerate()
+                .filter(|(_, variation)| variation.at == step)
+                .map(|(index, variation)| (index + 1, record.variation_positions(variation)[0].0.clone()))
+                .collect();
+            ui.display_variations(step + 1, &alternatives);
+        }
+        if step == line.moves.len() {
+            match (line.variation, record.result) {
+                (None, Some(winner)) => ui.display_game_over(&players[winner]),
+                (None, None) => ui.display_message("End of the record; the game was not finished."),
+                (Some(_), _) => ui.display_message("End of the variation."),
+            }
+        }
+
+        match ui.prompt_replay() {
+            ReplayCommand::Forward => step = (step + 1).min(line.moves.len()),
+            ReplayCommand::Back => step = step.saturating_sub(1),
+            ReplayCommand::Jump(entry) if (line.first..=line.first + line.moves.len()).contains(&entry) => {
+                step = entry - line.first;
+            }
+            ReplayCommand::Jump(entry) => ui.display_error(&format!("There is no entry {} here.", entry)),
+            ReplayCommand::Variation(number) if (1..=record.variations.len()).contains(&number) => {
+                line = Line::variation(&record, number - 1);
+                step = 0;
+            }
+            ReplayCommand::Variation(number) => ui.display_error(&format!("There is no variation v{}.", number)),
+            // Back to the entry the variation branched from
+            ReplayCommand::MainLine if line.variation.is_some() => {
+                step = line.first;
+                line = Line::main(&record);
+            }
+            ReplayCommand::MainLine => ui.display_message("Already on the main line."),
+            ReplayCommand::Branch => {
+                // A branch from the main line replaces its next entry; one
+                // from a variation takes the variation so far with it
+                let (at, prefix) = match line.variation {
+                    Some(index) => (record.variations[index].at, line.moves[..step].to_vec()),
+                    None if step < line.moves.len() => (step, Vec::new()),
+                    None => {
+                        ui.display_error("The record ends here, so there is no entry to try an alternative to.");
+                        continue;
+                    }
+                };
+                let mut replay = record.replay_to(at);
+                for &entry in &prefix {
+                    replay.apply(entry).expect("recorded games replay cleanly");
+                }
+
+                let branch = Branch { ui: &mut ui, players: &players, player_colors: &player_colors, first: at };
+                if let Some(moves) = branch.play(replay, prefix) {
+                    record.variations.push(Variation { at, moves });
+                    record.save(Path::new(path))?;
+                    ui.display_message(&format!("Saved as variation v{} in {}.", record.variations.len(), path));
+                    line = Line::variation(&record, record.variations.len() - 1);
+                    step = line.moves.len();
+                }
+            }
+            ReplayCommand::Quit => return Ok(()),
+        }
+    }
+}
+
+// The main line or one variation, as stepped through in the replay
+struct Line {
+    variation: Option<usize>,
+    first: usize, // Entries played before the line starts
+    start: Board,
+    moves: Vec<RecordedMove>,
+    positions: Vec<(String, Board)>,
+}
+
+impl Line {
+    fn main(record: &GameRecord) -> Self {
+        Line {
+            variation: None,
+            first: 0,
+            start: record.start.clone(),
+            moves: record.moves.clone(),
+            positions: record.positions(),
+        }
+    }
+
+    fn variation(record: &GameRecord, index: usize) -> Self {
+        let variation = &record.variations[index];
+        Line {
+            variation: Some(index),
+            first: variation.at,
+            start: record.replay_to(variation.at).board().clone(),
+            moves: variation.moves.clone(),
+            positions: record.variation_positions(variation),
+        }
+    }
+}
+
+// A what-if line being played from a position of the record
+struct Branch<'a> {
+    ui: &'a mut UI,
+    players: &'a [Player],
+    player_colors: &'a HashMap<PlayerId, Color>,
+    first: usize, // Entries played before the branch
+}
+
+impl Branch<'_> {
+    // Play moves until the branch is kept or dropped. Returns every move of
+    // the branch, starting with `moves`, or None if it was dropped.
+    fn play(mut self, mut replay: Replay, mut moves: Vec<RecordedMove>) -> Option<Vec<RecordedMove>> {
+        let already_played = moves.len();
+        let mut rng = rand::thread_rng();
+
+        loop {
+            let game_over = replay.winner().is_some();
+            if !game_over {
+                let player_id = replay.board().to_move();
+                self.ui.display_board(replay.board(), self.player_colors, player_id, None);
+                self.ui.display_player_turn(&self.players[player_id]);
+            }
+
+            match self.ui.prompt_branch(game_over) {
+                BranchCommand::Roll(forced) => {
+                    let dice_value = forced.unwrap_or_else(|| rng.gen_range(1..=6));
+                    if let Some(entry) = self.choose(replay.board(), dice_value) {
+                        self.apply(&mut replay, &mut moves, entry);
+                    }
+                }
+                BranchCommand::Bots(spec) => {
+                    let mut bots = Vec::new();
+                    for player in self.players {
+                        match bot::from_spec(&spec, &player.name) {
+                            Ok(bot) => bots.push(bot),
+                            Err(err) => {
+                                self.ui.display_error(&err);
+                                break;
+                            }
+                        }
+                    }
+                    if bots.len() < self.players.len() {
+                        continue;
+                    }
+
+                    while replay.winner().is_none() {
+                        let player_id = replay.board().to_move();
+                        let dice_value = rng.gen_range(1..=6);
+                        let legal_moves = replay.board().legal_moves(player_id, dice_value as usize);
+                        let entry = if legal_moves.is_empty() {
+                            RecordedMove::Pass { player_id, dice_value }
+                        } else {
+                            let piece_idx = bots[player_id].choose_piece(replay.board(), player_id, dice_value, &legal_moves);
+                            RecordedMove::Move { player_id, dice_value, piece_idx }
+                        };
+                        self.apply(&mut replay, &mut moves, entry);
+                    }
+                }
+                BranchCommand::Keep if moves.len() > already_played => return Some(moves),
+                BranchCommand::Keep => {
+                    self.ui.display_message("Nothing has been played in this branch yet.");
+                }
+                BranchCommand::Drop => return None,
+            }
+        }
+    }
+
+    // The move the player to move picks with a roll, or None if they
+    // change their mind
+    fn choose(&mut self, board: &Board, dice_value: u8) -> Option<RecordedMove> {
+        let player_id = board.to_move();
+        self.ui.display_dice_roll(dice_value);
+        let legal_moves = board.legal_moves(player_id, dice_value as usize);
+        if legal_moves.is_empty() {
+            self.ui.display_no_valid_moves();
+            return Some(RecordedMove::Pass { player_id, dice_value });
+        }
+
+        loop {
+            match self.ui.choose_piece(&legal_moves) {
+                PieceChoice::Piece(piece_idx) => return Some(RecordedMove::Move { player_id, dice_value, piece_idx }),
+                PieceChoice::Hint => {
+                    let hint = advisor::hint(board, player_id, dice_value, &legal_moves);
+                    self.ui.display_hint(&hint);
+                }
+                PieceChoice::Quit => return None,
+            }
+        }
+    }
+
+    fn apply(&mut self, replay: &mut Replay, moves: &mut Vec<RecordedMove>, entry: RecordedMove) {
+        let annotation = replay.apply(entry).expect("only legal moves are played");
+        moves.push(entry);
+        self.ui.display_message(&format!("{}. {}", self.first + moves.len(), annotation));
+        if let Some(winner) = replay.winner() {
+            self.ui.display_game_over(&self.players[winner]);
+        }
+    }
+}
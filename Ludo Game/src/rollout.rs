@@ -0,0 +1,136 @@
+use crate::board::{Board, MoveResult, PieceLocation};
+use crate::player::PlayerId;
+use rand::Rng;
+
+pub const DEFAULT_ROLLOUTS: usize = 200;
+
+// Estimate each player's chance of winning from a position by playing it
+// out many times with random dice and a quick greedy policy. Players who
+// resigned are skipped, so they never win.
+pub fn win_probabilities<R: Rng>(
+    board: &Board,
+    to_move: PlayerId,
+    resigned: &[PlayerId],
+    rollouts: usize,
+    rng: &mut R,
+) -> Vec<f64> {
+    let mut wins = vec![0usize; board.player_count()];
+
+    for _ in 0..rollouts {
+        wins[play_out(board.clone(), to_move, resigned, rng)] += 1;
+    }
+
+    wins.iter().map(|&count| count as f64 / rollouts.max(1) as f64).collect()
+}
+
+// Who moves after `player_id` played a move with this result. Same rules as
+// `Game`: a capture or a six earns another turn, and players who resigned
+// are passed over.
+pub fn next_to_move(
+    board: &Board,
+    player_id: PlayerId,
+    result: &MoveResult,
+    dice_value: u8,
+    resigned: &[PlayerId],
+) -> PlayerId {
+    if *result == MoveResult::Captured || dice_value == 6 {
+        player_id
+    } else {
+        next_seat(board, player_id, resigned)
+    }
+}
+
+// The next player round the table who is still playing
+fn next_seat(board: &Board, player_id: PlayerId, resigned: &[PlayerId]) -> PlayerId {
+    let mut next = (player_id + 1) % board.player_count();
+    while resigned.contains(&next) && next != player_id {
+        next = (next + 1) % board.player_count();
+    }
+    next
+}
+
+// Play to the end and return the winner
+fn play_out<R: Rng>(mut board: Board, mut to_move: PlayerId, resigned: &[PlayerId], rng: &mut R) -> PlayerId {
+    for player_id in 0..board.player_count() {
+        if board.has_won(player_id) {
+            return player_id;
+        }
+    }
+
+    loop {
+        let dice_value = rng.gen_range(1..=6u8);
+        let legal_moves = board.legal_moves(to_move, dice_value as usize);
+
+        if legal_moves.is_empty() {
+            to_move = next_seat(&board, to_move, resigned);
+            continue;
+        }
+
+        let piece_idx = quick_choice(&board, to_move, dice_value as usize, &legal_moves);
+        let result = board.move_piece(to_move, piece_idx, dice_value as usize);
+        if result == MoveResult::Finished && board.has_won(to_move) {
+            return to_move;
+        }
+        to_move = next_to_move(&board, to_move, &result, dice_value, resigned);
+    }
+}
+
+// Cheap stand-in for a real bot: capture if possible, then finish, then
+// reach the home track, then leave the yard, otherwise push the piece that
+// is furthest ahead
+fn quick_choice(board: &Board, player_id: PlayerId, steps: usize, legal_moves: &[usize]) -> usize {
+    let priority = |piece_idx: usize| {
+        let from = board.get_piece_location(player_id, piece_idx);
+        let to = board.destination(player_id, piece_idx, steps).unwrap();
+        let captures = !board.captures(player_id, piece_idx, steps).is_empty();
+
+        let rank = match (from, to) {
+            _ if captures => 4,
+            (_, PieceLocation::Finished) => 3,
+            (PieceLocation::MainTrack(_), PieceLocation::HomeTrack(_)) => 2,
+            (PieceLocation::Yard, _) => 1,
+            _ => 0,
+        };
+        (rank, board.progress(player_id, piece_idx))
+    };
+
+    *legal_moves.iter().max_by_key(|&&piece_idx| priority(piece_idx)).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    // A player who resigned has left the table: they are passed over and
+    // never win
+    #[test]
+    fn resigned_players_are_skipped() {
+        let board = Board::from_notation("t10yyy/yyyy/t20yyy/yyyy 0 - classic").unwrap();
+        let resigned = [1, 3];
+        assert_eq!(next_to_move(&board, 0, &MoveResult::Moved, 3, &resigned), 2);
+        assert_eq!(next_to_move(&board, 2, &MoveResult::Moved, 3, &resigned), 0);
+        assert_eq!(next_to_move(&board, 2, &MoveResult::Moved, 6, &resigned), 2);
+        assert_eq!(next_to_move(&board, 0, &MoveResult::Moved, 3, &[]), 1);
+
+        let mut rng = StdRng::seed_from_u64(43);
+        let probabilities = win_probabilities(&board, 0, &resigned, 200, &mut rng);
+        assert_eq!((probabilities[1], probabilities[3]), (0.0, 0.0));
+        assert!((probabilities[0] + probabilities[2] - 1.0).abs() < 1e-9);
+        let seated = win_probabilities(&board, 0, &[], 200, &mut rng);
+        assert!(seated[1] > 0.0 && seated[3] > 0.0, "{:?}", seated);
+    }
+
+    #[test]
+    fn quick_choice_prefers_real_captures() {
+        // Piece 1 can capture on square 14
+        let board = Board::from_notation("yt10t30y/t14yyy 0 - classic").unwrap();
+        assert_eq!(quick_choice(&board, 0, 4, &board.legal_moves(0, 4)), 1);
+
+        // Leaving the yard onto the other player's piece captures nothing,
+        // so finishing comes first
+        let board = Board::from_notation("yh0fy/t0yyy 0 - classic").unwrap();
+        assert_eq!(quick_choice(&board, 0, 6, &board.legal_moves(0, 6)), 1);
+    }
+}
@@ -0,0 +1,411 @@
+use crate::board::{Board, RULESET_ID};
+use crate::engine;
+use crate::game::GameStats;
+use crate::player::PlayerId;
+use crate::seal::{self, Seal};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+// Version of the save file layout written by this build. Older saves are
+// brought up to date by `MIGRATIONS` when they are loaded; every version
+// has saves in tests/saves that must keep loading.
+pub const SAVE_VERSION: u32 = 2;
+pub const DEFAULT_SAVE_PATH: &str = "ludo.save";
+const FORMAT: &str = "ludo-save";
+
+// Where interactive games are saved after every turn: $LUDO_AUTOSAVE if
+// set, otherwise ~/.ludo/autosave.save
+pub fn autosave_path() -> PathBuf {
+    if let Some(path) = env::var_os("LUDO_AUTOSAVE") {
+        return PathBuf::from(path);
+    }
+    match env::var_os("HOME") {
+        Some(home) => Path::new(&home).join(".ludo").join("autosave.save"),
+        None => PathBuf::from("ludo-autosave.save"),
+    }
+}
+
+pub struct SavedPlayer {
+    pub name: String,
+    pub controller: Option<String>, // Bot spec, None for a human
+    pub resigned: bool,
+}
+
+// Everything needed to carry on a game where it was left, taken at the
+// start of a turn. Saved games are plain `key = value` text files.
+pub struct SavedGame {
+    pub players: Vec<SavedPlayer>,
+    pub board: Board, // Including the player to move and any pending bonus turn
+    pub dice_sides: u8,
+    pub dice_seed: u64,
+    pub dice_keyed: bool, // Rolling from the seed signed with the league key
+    pub dice_rolls: u64, // Replayed on load to bring the dice back to the same state
+    pub pending_roll: Option<u8>, // A roll taken back by undo, to be played again
+    pub first_player: PlayerId,
+    pub stats: GameStats,
+    pub checksum: Option<String>, // As read from the file, see `seal`
+}
+
+impl SavedGame {
+    // Saves whose checksum shows they were edited are not loaded
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("cannot read '{}': {}", path.display(), err))?;
+        let saved = Self::parse(&text).map_err(|err| format!("'{}': {}", path.display(), err))?;
+        saved.seal(seal::league_key().as_deref()).map_err(|err| format!("'{}': {}", path.display(), err))?;
+        Ok(saved)
+    }
+
+    // Written to a temporary file first and renamed into place, so a crash
+    // while saving never leaves a half-written save behind
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|err| format!("cannot create '{}': {}", dir.display(), err))?;
+        }
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, self.to_text()).map_err(|err| format!("cannot write '{}': {}", temporary.display(), err))?;
+        fs::rename(&temporary, path).map_err(|err| format!("cannot write '{}': {}", path.display(), err))
+    }
+
+    // Signed with the league key, if there is one
+    pub fn to_text(&self) -> String {
+        self.to_text_with_key(seal::league_key().as_deref())
+    }
+
+    fn to_text_with_key(&self, key: Option<&str>) -> String {
+        let mut text = self.signed_text();
+        if let Some(key) = key {
+            text.push_str(&format!("checksum = {}\n", seal::sign(key, &text)));
+        }
+        text
+    }
+
+    // Check the save's checksum. Fails if the save was changed since it
+    // was written, or written with another key.
+    pub fn seal(&self, key: Option<&str>) -> Result<Seal, String> {
+        seal::check(key, &self.signed_text(), self.checksum.as_deref())
+    }
+
+    // Everything but the checksum, which covers all of it: the players, the
+    // dice and how far they have rolled, the counters and the position
+    fn signed_text(&self) -> String {
+        let mut lines = vec![
+            "# Ludo saved game".to_string(),
+            format!("format = {}", FORMAT),
+            format!("version = {}", SAVE_VERSION),
+            format!("players = {}", self.players.len()),
+        ];
+        for (player_id, player) in self.players.iter().enumerate() {
+            lines.push(format!("player{}.name = {}", player_id, player.name));
+            lines.push(format!("player{}.controller = {}", player_id, player.controller.as_deref().unwrap_or("human")));
+            lines.push(format!("player{}.resigned = {}", player_id, player.resigned));
+        }
+        lines.extend([
+            format!("dice.sides = {}", self.dice_sides),
+            format!("dice.seed = {}", self.dice_seed),
+        ]);
+        // Only in league games, whose dice roll from the seed signed with
+        // the league key
+        if self.dice_keyed {
+            lines.push("dice.keyed = true".to_string());
+        }
+        lines.push(format!("dice.rolls = {}", self.dice_rolls));
+        // Only while a roll is waiting to be played again, so saves without
+        // one read the same in builds from before undo
+        if let Some(roll) = self.pending_roll {
+            lines.push(format!("pending_roll = {}", roll));
+        }
+        lines.extend([
+            format!("first_player = {}", self.first_player),
+            format!("turns = {}", self.stats.turns),
+            format!("captures = {}", self.stats.captures),
+            format!("sixes = {}", self.stats.sixes),
+            format!("position = {}", self.board.to_notation()),
+        ]);
+
+        let mut text = lines.join("\n");
+        text.push('\n');
+        text
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut fields = Fields::parse(text)?;
+
+        if fields.text("format")? != FORMAT {
+            return Err("not a saved game".to_string());
+        }
+        let version: u32 = fields.get("version")?;
+        if version > SAVE_VERSION {
+            return Err(format!(
+                "saved by a newer version of the game (save version {}, this build reads up to {}); update the game to load it",
+                version, SAVE_VERSION
+            ));
+        }
+        if version == 0 {
+            return Err("invalid save version 0".to_string());
+        }
+        for migration in &MIGRATIONS[version as usize - 1..] {
+            migration(&mut fields).map_err(|err| format!("cannot upgrade this version {} save: {}", version, err))?;
+        }
+
+        let player_count: usize = fields.get("players")?;
+        let mut players = Vec::new();
+        for player_id in 0..player_count {
+            let controller = fields.text(&format!("player{}.controller", player_id))?;
+            players.push(SavedPlayer {
+                name: fields.text(&format!("player{}.name", player_id))?.to_string(),
+                controller: if controller == "human" { None } else { Some(controller.to_string()) },
+                resigned: fields.get(&format!("player{}.resigned", player_id))?,
+            });
+        }
+
+        let board = Board::from_notation(fields.text("position")?)?;
+        if board.player_count() != player_count {
+            return Err(format!("position is for {} players, but {} are listed", board.player_count(), player_count));
+        }
+
+        let first_player: PlayerId = fields.get("first_player")?;
+        if first_player >= player_count {
+            return Err(format!("first player {} is not in the game", first_player));
+        }
+
+        let dice_sides: u8 = fields.get("dice.sides")?;
+        if dice_sides == 0 {
+            return Err("dice need at least one side".to_string());
+        }
+        let dice_keyed = match fields.text("dice.keyed") {
+            Ok(_) => fields.get("dice.keyed")?,
+            Err(_) => false,
+        };
+        let pending_roll = match fields.text("pending_roll") {
+            Ok(_) => match fields.get("pending_roll")? {
+                roll if (1..=dice_sides).contains(&roll) => Some(roll),
+                roll => return Err(format!("pending roll {} is not on the dice", roll)),
+            },
+            Err(_) => None,
+        };
+
+        Ok(SavedGame {
+            players,
+            board,
+            dice_sides,
+            dice_seed: fields.get("dice.seed")?,
+            dice_keyed,
+            dice_rolls: fields.get("dice.rolls")?,
+            pending_roll,
+            first_player,
+            stats: GameStats {
+                turns: fields.get("turns")?,
+                captures: fields.get("captures")?,
+                sixes: fields.get("sixes")?,
+            },
+            checksum: fields.text("checksum").ok().map(str::to_string),
+        })
+    }
+}
+
+// Upgrades from each save version to the next, starting from version 1.
+// They work on the raw fields, so they never depend on how the current
+// version is read.
+type Migration = fn(&mut Fields) -> Result<(), String>;
+const MIGRATIONS: [Migration; SAVE_VERSION as usize - 1] = [v1_to_v2];
+
+// Version 2 keeps the position in `Board` notation, which includes the
+// player to move, any bonus turn and the ruleset, so those fields go. It
+// also always says whether each player resigned; the earliest version 1
+// saves date from before players could.
+fn v1_to_v2(fields: &mut Fields) -> Result<(), String> {
+    let ruleset = fields.take("ruleset")?;
+    if ruleset != RULESET_ID {
+        return Err(format!("saved under the '{}' rules, but this build plays '{}'", ruleset, RULESET_ID));
+    }
+
+    let (mut board, to_move) = engine::decode_position(fields.text("position")?)?;
+    if fields.get::<PlayerId>("current_player")? != to_move {
+        return Err("'current_player' does not match the player to move in 'position'".to_string());
+    }
+    board.set_turn(to_move, fields.get("bonus_turn")?);
+    fields.take("current_player")?;
+    fields.take("bonus_turn")?;
+    fields.set("position", board.to_notation());
+
+    for player_id in 0..fields.get::<usize>("players")? {
+        let key = format!("player{}.resigned", player_id);
+        if fields.text(&key).is_err() {
+            fields.set(&key, "false".to_string());
+        }
+    }
+    Ok(())
+}
+
+// The `key = value` lines of a saved game
+struct Fields(HashMap<String, String>);
+
+impl Fields {
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut fields = HashMap::new();
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            let (key, value) = line.split_once('=').ok_or_else(|| format!("expected 'key = value', found '{}'", line))?;
+            fields.insert(key.trim().to_string(), value.trim().to_string());
+        }
+        Ok(Fields(fields))
+    }
+
+    fn text(&self, key: &str) -> Result<&str, String> {
+        self.0.get(key).map(String::as_str).ok_or_else(|| format!("missing '{}'", key))
+    }
+
+    fn get<T: FromStr>(&self, key: &str) -> Result<T, String> {
+        let value = self.text(key)?;
+        value.parse().map_err(|_| format!("invalid value '{}' for '{}'", value, key))
+    }
+
+    // Remove a field, for migrations
+    fn take(&mut self, key: &str) -> Result<String, String> {
+        self.0.remove(key).ok_or_else(|| format!("missing '{}'", key))
+    }
+
+    fn set(&mut self, key: &str, value: String) {
+        self.0.insert(key.to_string(), value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    // Saves written by every released version of the game, by file name
+    fn corpus() -> BTreeMap<String, String> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("saves");
+        fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .map(|path| (path.file_name().unwrap().to_string_lossy().into_owned(), fs::read_to_string(&path).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn every_save_in_the_corpus_loads() {
+        let saves = corpus();
+        for version in 1..=SAVE_VERSION {
+            let prefix = format!("v{}-", version);
+            assert!(
+                saves.iter().any(|(name, _)| name.starts_with(&prefix)),
+                "tests/saves has no save from version {}",
+                version
+            );
+        }
+
+        for (name, text) in &saves {
+            let saved = SavedGame::parse(text).unwrap_or_else(|err| panic!("{}: {}", name, err));
+            // Upgraded saves are written back in the current version
+            let upgraded = saved.to_text();
+            assert!(upgraded.contains(&format!("version = {}\n", SAVE_VERSION)));
+            assert_eq!(SavedGame::parse(&upgraded).unwrap().to_text(), upgraded, "{}", name);
+        }
+    }
+
+    #[test]
+    fn version_1_saves_keep_their_game() {
+        let saves = corpus();
+
+        let saved = SavedGame::parse(&saves["v1-before-resigning.save"]).unwrap();
+        assert_eq!(saved.board.to_notation(), "h5t17yy/ft32yy/fyyy 0 b classic");
+        assert!(saved.players.iter().all(|player| !player.resigned));
+        assert_eq!(saved.players[2].controller.as_deref(), Some("aggressive"));
+        assert_eq!((saved.dice_rolls, saved.stats.turns, saved.stats.captures), (116, 116, 2));
+
+        let saved = SavedGame::parse(&saves["v1-resigned.save"]).unwrap();
+        assert_eq!(saved.board.to_notation(), "h4t6yy/yyyy/h5yyy 0 - classic");
+        let resigned: Vec<bool> = saved.players.iter().map(|player| player.resigned).collect();
+        assert_eq!(resigned, [false, true, false]);
+    }
+
+    #[test]
+    fn rejects_saves_it_cannot_read() {
+        let saves = corpus();
+        let current = &saves["v2-four-players.save"];
+
+        let newer = current.replace("version = 2", "version = 3");
+        let err = SavedGame::parse(&newer).err().expect("saves from newer versions are rejected");
+        assert!(err.contains("newer version"), "{}", err);
+
+        let old = &saves["v1-resigned.save"];
+        for (from, to) in [
+            ("ruleset = classic", "ruleset = house"),
+            ("current_player = 0", "current_player = 1"),
+            ("bonus_turn = false\n", ""),
+            ("version = 1", "version = 0"),
+        ] {
+            assert!(SavedGame::parse(&old.replace(from, to)).is_err(), "accepted {} -> {}", from, to);
+        }
+    }
+
+    #[test]
+    fn league_checksums_catch_edited_saves() {
+        let saves = corpus();
+        let unsigned = SavedGame::parse(&saves["v2-four-players.save"]).unwrap();
+        assert_eq!(unsigned.seal(Some("league")), Ok(Seal::Missing));
+        assert_eq!(unsigned.seal(None), Ok(Seal::Unchecked));
+
+        let signed = unsigned.to_text_with_key(Some("league"));
+        let saved = SavedGame::parse(&signed).unwrap();
+        assert_eq!(saved.seal(Some("league")), Ok(Seal::Valid));
+        assert!(saved.seal(Some("another league")).is_err());
+
+        for (from, to) in [
+            ("dice.rolls = 64", "dice.rolls = 63"),         // Rolled again for a better number
+            ("t26yyy 2", "t32yyy 2"),                       // Moved a piece by hand
+            ("player0.resigned = true", "player0.resigned = false"),
+        ] {
+            let edited = SavedGame::parse(&signed.replace(from, to)).unwrap();
+            assert!(edited.seal(Some("league")).is_err(), "accepted {} -> {}", from, to);
+        }
+    }
+
+    #[test]
+    fn keyed_dice_are_saved() {
+        let saves = corpus();
+        let mut saved = SavedGame::parse(&saves["v2-four-players.save"]).unwrap();
+        assert!(!saved.dice_keyed);
+
+        saved.dice_keyed = true;
+        let signed = saved.to_text_with_key(Some("league"));
+        assert!(signed.contains("\ndice.keyed = true\n"));
+        let loaded = SavedGame::parse(&signed).unwrap();
+        assert!(loaded.dice_keyed);
+        assert_eq!(loaded.seal(Some("league")), Ok(Seal::Valid));
+
+        // Dropping the line would make the dice roll from the written seed
+        let dropped = SavedGame::parse(&signed.replace("dice.keyed = true\n", "")).unwrap();
+        assert!(!dropped.dice_keyed);
+        assert!(dropped.seal(Some("league")).is_err());
+        assert!(SavedGame::parse(&signed.replace("dice.keyed = true", "dice.keyed = yes")).is_err());
+    }
+
+    // A roll kept by undo must survive saving, or quitting and loading
+    // would give a fresh one
+    #[test]
+    fn pending_rolls_are_saved() {
+        let saves = corpus();
+        let mut saved = SavedGame::parse(&saves["v2-four-players.save"]).unwrap();
+        assert_eq!(saved.pending_roll, None);
+
+        saved.pending_roll = Some(4);
+        let signed = saved.to_text_with_key(Some("league"));
+        assert!(signed.contains("\npending_roll = 4\n"));
+        let loaded = SavedGame::parse(&signed).unwrap();
+        assert_eq!(loaded.pending_roll, Some(4));
+        assert_eq!(loaded.seal(Some("league")), Ok(Seal::Valid));
+
+        let dropped = SavedGame::parse(&signed.replace("pending_roll = 4\n", "")).unwrap();
+        assert_eq!(dropped.pending_roll, None);
+        assert!(dropped.seal(Some("league")).is_err());
+        for roll in ["0", "7", "four"] {
+            assert!(SavedGame::parse(&signed.replace("pending_roll = 4", &format!("pending_roll = {}", roll))).is_err());
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::env;
+
+// Saves and game records can carry a keyed checksum, an HMAC-SHA256 of
+// their contents under a key shared by everyone in a league. Anyone with
+// the key can check a file from any machine; without it, a file edited by
+// hand cannot be given a matching checksum.
+const ALGORITHM: &str = "hmac-sha256";
+
+// What the checksum of a loaded file showed. A checksum that does not
+// match is an error rather than a `Seal`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Seal {
+    Valid,     // Written with the league key and unchanged since
+    Missing,   // A league key is set but the file has no checksum
+    Unchecked, // No league key is set, so nothing could be checked
+}
+
+// The league key from $LUDO_LEAGUE_KEY, if set
+pub fn league_key() -> Option<String> {
+    env::var("LUDO_LEAGUE_KEY").ok().filter(|key| !key.is_empty())
+}
+
+// Checksum of `text` under `key`, as written into files
+pub fn sign(key: &str, text: &str) -> String {
+    let digest: String = mac(key, text)
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("{}:{}", ALGORITHM, digest)
+}
+
+// Check the checksum a file gives for `text`
+pub fn check(key: Option<&str>, text: &str, checksum: Option<&str>) -> Result<Seal, String> {
+    let (key, checksum) = match (key, checksum) {
+        (None, _) => return Ok(Seal::Unchecked),
+        (Some(_), None) => return Ok(Seal::Missing),
+        (Some(key), Some(checksum)) => (key, checksum),
+    };
+
+    let digest = checksum
+        .strip_prefix(ALGORITHM)
+        .and_then(|rest| rest.strip_prefix(':'))
+        .and_then(decode_hex)
+        .ok_or_else(|| format!("unrecognised checksum '{}'", checksum))?;
+    mac(key, text).verify_slice(&digest).map_err(|_| {
+        "the checksum does not match: the file was changed after it was written, or written with a different league key"
+            .to_string()
+    })?;
+    Ok(Seal::Valid)
+}
+
+// The seed the dice of a league game really roll from: the seed written
+// into saves, signed with the league key. Anyone can read the written seed,
+// but only the key turns it into rolls.
+pub fn dice_seed(key: &str, seed: u64) -> u64 {
+    let digest = mac(key, &format!("dice {}", seed)).finalize().into_bytes();
+    u64::from_le_bytes(digest[..8].try_into().expect("SHA-256 digests are 32 bytes"))
+}
+
+fn mac(key: &str, text: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(text.as_bytes());
+    mac
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,60 @@
+use crate::board::RULESET_ID;
+use crate::cli::Options;
+use crate::game::StartRule;
+use crate::sim::{self, ordinal, SimConfig};
+
+// Measure how much sitting first (or moving first) is worth. Every seat is
+// played by the same bot, so any difference comes from the position alone.
+pub fn run(options: &Options) -> Result<(), String> {
+    let bot = options.get_str("bot").unwrap_or("balanced").to_string();
+    let games = options.get_or("games", 20000usize)?;
+    let seed = options.get_or("seed", 1u64)?;
+    let threads = options.get_or("threads", sim::default_threads())?;
+
+    if games == 0 || threads == 0 {
+        return Err("--games and --threads must be at least 1".to_string());
+    }
+
+    println!("Seat advantage for '{}' bots, {} games per setting, ruleset '{}'", bot, games, RULESET_ID);
+
+    for start_rule in [StartRule::FirstSeat, StartRule::RollOff] {
+        for players in 2..=4 {
+            let config = SimConfig {
+                specs: vec![bot.clone(); players],
+                games,
+                seed,
+                rotate: false,
+                threads,
+                start_rule,
+            };
+            let totals = sim::simulate(&config)?;
+
+            let label = match start_rule {
+                StartRule::FirstSeat => "seat 1 starts",
+                StartRule::RollOff => "roll-off decides who starts",
+            };
+            println!("\n{} players, {}:", players, label);
+            println!("  {:<10} {:>18}   {:<12} {:>18}", "Seat", "Win rate", "Turn order", "Win rate");
+
+            for i in 0..players {
+                println!(
+                    "  {:<10} {:>18}   {:<12} {:>18}",
+                    format!("Seat {}", i + 1),
+                    rate_with_interval(totals.seat_wins()[i], totals.games()),
+                    format!("Moved {}", ordinal(i + 1)),
+                    rate_with_interval(totals.order_wins()[i], totals.games())
+                );
+            }
+            println!("  Fair share: {:.1}%", 100.0 / players as f64);
+        }
+    }
+
+    Ok(())
+}
+
+// "27.3% ± 0.6%", using a 95% normal-approximation interval
+fn rate_with_interval(wins: usize, games: usize) -> String {
+    let rate = wins as f64 / games as f64;
+    let margin = 1.96 * (rate * (1.0 - rate) / games as f64).sqrt();
+    format!("{:.1}% ± {:.1}%", rate * 100.0, margin * 100.0)
+}
@@ -0,0 +1,331 @@
+use crate::bot::{self, PlayerController};
+use crate::cli::Options;
+use crate::dice::derive_seed;
+use crate::game::{Game, GameStats, StartRule};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+// Play many bot-only games and print aggregate results
+pub fn run(options: &Options) -> Result<(), String> {
+    let specs: Vec<String> = options
+        .get_str("bots")
+        .unwrap_or("balanced,balanced")
+        .split(',')
+        .map(|spec| spec.trim().to_string())
+        .collect();
+    let games = options.get_or("games", 1000usize)?;
+    let seed = options.get_or("seed", 1u64)?;
+    let rotate = options.get_or("rotate", true)?;
+    let threads = options.get_or("threads", default_threads())?;
+    let start_rule = options.get_or("start", StartRule::FirstSeat)?;
+
+    if !(2..=4).contains(&specs.len()) {
+        return Err("--bots needs between 2 and 4 comma-separated bots".to_string());
+    }
+    if games == 0 || threads == 0 {
+        return Err("--games and --threads must be at least 1".to_string());
+    }
+    // Fail early on a bad spec rather than in the middle of the run
+    for spec in &specs {
+        bot::from_spec(spec, spec)?;
+    }
+
+    let config = SimConfig {
+        specs,
+        games,
+        seed,
+        rotate,
+        threads,
+        start_rule,
+    };
+    let totals = simulate(&config)?;
+    totals.print(&config.specs);
+    Ok(())
+}
+
+// Everything that decides the outcome of a batch of simulated games
+pub struct SimConfig {
+    pub specs: Vec<String>, // One bot spec per seat
+    pub games: usize,
+    pub seed: u64,
+    pub rotate: bool,
+    pub threads: usize,
+    pub start_rule: StartRule,
+}
+
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+// Play `games` games spread over `threads` worker threads. Every game's dice
+// come from its index and the master seed, and totals are plain sums, so
+// the result does not depend on the thread count or scheduling.
+pub fn simulate(config: &SimConfig) -> Result<Totals, String> {
+    let players = config.specs.len();
+    let partials = run_workers(config, || Totals::new(players), |totals, _, summary| totals.add(&summary))?;
+
+    let mut totals = Totals::new(players);
+    for partial in partials {
+        totals.merge(&partial);
+    }
+    Ok(totals)
+}
+
+// Like `simulate`, but keep every game's summary, in game order
+pub fn play_games(config: &SimConfig) -> Result<Vec<GameSummary>, String> {
+    let partials = run_workers(config, Vec::new, |games, game_idx, summary| games.push((game_idx, summary)))?;
+
+    let mut games: Vec<(usize, GameSummary)> = partials.into_iter().flatten().collect();
+    games.sort_by_key(|&(game_idx, _)| game_idx);
+    Ok(games.into_iter().map(|(_, summary)| summary).collect())
+}
+
+// Hand out game indices to worker threads; each worker builds its bots
+// once and folds the games it plays into its own accumulator
+fn run_workers<A, N, F>(config: &SimConfig, new: N, add: F) -> Result<Vec<A>, String>
+where
+    A: Send,
+    N: Fn() -> A + Sync,
+    F: Fn(&mut A, usize, GameSummary) + Sync,
+{
+    let next_game = AtomicUsize::new(0);
+
+    let results: Vec<Result<A, String>> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..config.threads.min(config.games))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut bots = Vec::new();
+                    for spec in &config.specs {
+                        bots.push(Some(bot::from_spec(spec, spec)?));
+                    }
+                    let mut acc = new();
+                    loop {
+                        let game_idx = next_game.fetch_add(1, Ordering::Relaxed);
+                        if game_idx >= config.games {
+                            return Ok(acc);
+                        }
+                        add(&mut acc, game_idx, play_one(config, game_idx, &mut bots));
+                    }
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap_or_else(|_| Err("a simulation thread panicked".to_string())))
+            .collect()
+    });
+
+    results.into_iter().collect()
+}
+
+// Outcome of a single simulated game
+pub struct GameSummary {
+    pub seating: Vec<usize>, // Index into the bot list for each seat
+    pub winner_seat: usize,
+    pub first_seat: usize,
+    pub stats: GameStats,
+}
+
+// Play game number `game_idx` of a batch with `bots`, one per entry of
+// `config.specs`, which are handed back afterwards. With `rotate` the bots
+// move one seat along every game so each bot gets to sit everywhere.
+fn play_one(config: &SimConfig, game_idx: usize, bots: &mut [Option<Box<dyn PlayerController>>]) -> GameSummary {
+    let players = config.specs.len();
+    let seating: Vec<usize> = (0..players)
+        .map(|seat| if config.rotate { (seat + game_idx) % players } else { seat })
+        .collect();
+
+    let seated = seating.iter().map(|&bot_idx| bots[bot_idx].take().expect("each bot sits once")).collect();
+    let mut game = Game::headless(seated, derive_seed(config.seed, game_idx as u64));
+    game.set_start_rule(config.start_rule);
+    let winner_seat = game.start().expect("bot-only games always finish");
+    let summary = GameSummary {
+        seating,
+        winner_seat,
+        first_seat: game.first_player(),
+        stats: game.stats(),
+    };
+
+    for (bot, &bot_idx) in game.into_bots().into_iter().zip(&summary.seating) {
+        bots[bot_idx] = Some(bot);
+    }
+    summary
+}
+
+// Running totals over a batch of games
+#[derive(Debug, PartialEq, Eq)]
+pub struct Totals {
+    games: usize,
+    seat_wins: Vec<usize>,
+    order_wins: Vec<usize>, // Wins by turn order: 0 = moved first
+    bot_wins: Vec<usize>,
+    bot_games: Vec<usize>,
+    turns: usize,
+    captures: usize,
+    sixes: usize,
+}
+
+impl Totals {
+    pub fn new(players: usize) -> Self {
+        Totals {
+            games: 0,
+            seat_wins: vec![0; players],
+            order_wins: vec![0; players],
+            bot_wins: vec![0; players],
+            bot_games: vec![0; players],
+            turns: 0,
+            captures: 0,
+            sixes: 0,
+        }
+    }
+
+    pub fn add(&mut self, summary: &GameSummary) {
+        self.games += 1;
+        let players = summary.seating.len();
+        self.seat_wins[summary.winner_seat] += 1;
+        self.order_wins[(summary.winner_seat + players - summary.first_seat) % players] += 1;
+        self.bot_wins[summary.seating[summary.winner_seat]] += 1;
+        for &bot_idx in &summary.seating {
+            self.bot_games[bot_idx] += 1;
+        }
+        self.turns += summary.stats.turns;
+        self.captures += summary.stats.captures;
+        self.sixes += summary.stats.sixes;
+    }
+
+    pub fn merge(&mut self, other: &Totals) {
+        self.games += other.games;
+        for seat in 0..self.seat_wins.len() {
+            self.seat_wins[seat] += other.seat_wins[seat];
+            self.order_wins[seat] += other.order_wins[seat];
+            self.bot_wins[seat] += other.bot_wins[seat];
+            self.bot_games[seat] += other.bot_games[seat];
+        }
+        self.turns += other.turns;
+        self.captures += other.captures;
+        self.sixes += other.sixes;
+    }
+
+    pub fn games(&self) -> usize {
+        self.games
+    }
+
+    pub fn seat_wins(&self) -> &[usize] {
+        &self.seat_wins
+    }
+
+    pub fn order_wins(&self) -> &[usize] {
+        &self.order_wins
+    }
+
+    pub fn print(&self, specs: &[String]) {
+        let games = self.games as f64;
+        let percent = |wins: usize, played: usize| 100.0 * wins as f64 / played.max(1) as f64;
+
+        println!("Simulated {} games", self.games);
+
+        println!("\nWin rate by seat:");
+        for (seat, &wins) in self.seat_wins.iter().enumerate() {
+            println!("  Seat {}: {:6} wins ({:5.1}%)", seat + 1, wins, percent(wins, self.games));
+        }
+
+        println!("\nWin rate by turn order:");
+        for (order, &wins) in self.order_wins.iter().enumerate() {
+            println!("  Moved {}: {:6} wins ({:5.1}%)", ordinal(order + 1), wins, percent(wins, self.games));
+        }
+
+        println!("\nWin rate by bot:");
+        let width = specs.iter().map(|spec| spec.len() + 2).max().unwrap_or(0);
+        for (bot_idx, spec) in specs.iter().enumerate() {
+            println!(
+                "  Bot {} {:<width$}: {:6} wins ({:5.1}%)",
+                bot_idx + 1,
+                format!("({})", spec),
+                self.bot_wins[bot_idx],
+                percent(self.bot_wins[bot_idx], self.bot_games[bot_idx])
+            );
+        }
+
+        println!("\nAverage game length: {:.1} turns", self.turns as f64 / games);
+        println!("Captures per game:   {:.2}", self.captures as f64 / games);
+        println!("Sixes per game:      {:.2}", self.sixes as f64 / games);
+    }
+}
+
+pub fn ordinal(n: usize) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", n, suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn results_do_not_depend_on_the_thread_count() {
+        for (specs, start_rule) in [
+            (vec!["balanced", "aggressive"], StartRule::FirstSeat),
+            (vec!["racing", "defensive", "balanced", "aggressive"], StartRule::RollOff),
+        ] {
+            let config = |threads| SimConfig {
+                specs: specs.iter().map(|spec| spec.to_string()).collect(),
+                games: 16,
+                seed: 29,
+                rotate: true,
+                threads,
+                start_rule,
+            };
+
+            let single = simulate(&config(1)).unwrap();
+            assert_eq!(single.games(), 16);
+            for threads in [3, 4] {
+                assert_eq!(simulate(&config(threads)).unwrap(), single, "{} threads", threads);
+            }
+
+            let games: Vec<usize> = play_games(&config(4)).unwrap().iter().map(|game| game.winner_seat).collect();
+            let in_order: Vec<usize> = play_games(&config(1)).unwrap().iter().map(|game| game.winner_seat).collect();
+            assert_eq!(games, in_order);
+        }
+    }
+
+    #[test]
+    fn bots_rotate_through_the_seats() {
+        let config = SimConfig {
+            specs: vec!["balanced".to_string(), "racing".to_string(), "defensive".to_string()],
+            games: 6,
+            seed: 30,
+            rotate: true,
+            threads: 1,
+            start_rule: StartRule::RollOff,
+        };
+        let seatings: Vec<Vec<usize>> = play_games(&config).unwrap().into_iter().map(|game| game.seating).collect();
+        assert_eq!(seatings, [[0, 1, 2], [1, 2, 0], [2, 0, 1], [0, 1, 2], [1, 2, 0], [2, 0, 1]]);
+
+        let fixed = SimConfig { rotate: false, ..config };
+        assert!(play_games(&fixed).unwrap().iter().all(|game| game.seating == [0, 1, 2]));
+    }
+
+    // Turn order counts round the table from whoever moved first
+    #[test]
+    fn wins_are_counted_by_seat_and_turn_order() {
+        let mut totals = Totals::new(4);
+        for (winner_seat, first_seat) in [(1, 2), (2, 2), (0, 3), (3, 0)] {
+            totals.add(&GameSummary {
+                seating: vec![3, 0, 1, 2],
+                winner_seat,
+                first_seat,
+                stats: GameStats::default(),
+            });
+        }
+        assert_eq!(totals.seat_wins(), [1, 1, 1, 1]);
+        assert_eq!(totals.order_wins(), [1, 1, 0, 2]);
+        assert_eq!(totals.bot_wins, [1, 1, 1, 1]);
+    }
+}
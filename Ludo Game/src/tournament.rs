@@ -0,0 +1,321 @@
+use crate::bot;
+use crate::cli::Options;
+use crate::dice::derive_seed;
+use crate::game::StartRule;
+use crate::sim::{self, SimConfig};
+use std::fs;
+use std::path::Path;
+
+const INITIAL_RATING: f64 = 1500.0;
+const K_FACTOR: f64 = 16.0;
+
+// One registered bot and its running results
+struct Entry {
+    name: String,
+    spec: String,
+    rating: f64,
+    games: usize,
+    wins: usize,
+    byes: usize, // Swiss rounds sat out
+}
+
+// Play bots against each other at tables of `--players`, either every
+// possible table once (round-robin) or several rounds where bots with similar
+// ratings meet (Swiss), and rank them by multiplayer Elo.
+pub fn run(options: &Options) -> Result<(), String> {
+    let specs: Vec<String> = options
+        .get_str("bots")
+        .unwrap_or("balanced,aggressive,defensive,racing")
+        .split(',')
+        .map(|spec| spec.trim().to_string())
+        .collect();
+    let format = options.get_str("format").unwrap_or("round-robin");
+    let players = options.get_or("players", 2usize)?;
+    let games = options.get_or("games", 100usize)?;
+    let rounds = options.get_or("rounds", 5usize)?;
+    let seed = options.get_or("seed", 1u64)?;
+    let threads = options.get_or("threads", sim::default_threads())?;
+
+    if !(2..=4).contains(&players) {
+        return Err("--players must be between 2 and 4".to_string());
+    }
+    if specs.len() < players {
+        return Err(format!("need at least {} bots for tables of {}", players, players));
+    }
+    if games == 0 || threads == 0 {
+        return Err("--games and --threads must be at least 1".to_string());
+    }
+
+    let mut entries = Vec::new();
+    for spec in &specs {
+        bot::from_spec(spec, spec)?;
+        let mut name = display_name(spec);
+        if entries.iter().any(|entry: &Entry| entry.name == name) {
+            name = format!("{}#{}", name, entries.len() + 1);
+        }
+        entries.push(Entry {
+            name,
+            spec: spec.clone(),
+            rating: INITIAL_RATING,
+            games: 0,
+            wins: 0,
+            byes: 0,
+        });
+    }
+
+    let mut match_idx = 0;
+    match format {
+        "round-robin" => {
+            let tables = combinations(entries.len(), players);
+            println!("Round-robin: {} tables of {}, {} games each", tables.len(), players, games);
+            for table in tables {
+                play_match(&mut entries, &table, games, derive_seed(seed, match_idx), threads)?;
+                match_idx += 1;
+            }
+        }
+        "swiss" => {
+            println!("Swiss: {} rounds at tables of {}, {} games each", rounds, players, games);
+            for _ in 0..rounds {
+                let tables = swiss_tables(&entries, players);
+                for (idx, entry) in entries.iter_mut().enumerate() {
+                    if !tables.iter().any(|table| table.contains(&idx)) {
+                        entry.byes += 1;
+                    }
+                }
+                for table in tables {
+                    play_match(&mut entries, &table, games, derive_seed(seed, match_idx), threads)?;
+                    match_idx += 1;
+                }
+            }
+        }
+        other => return Err(format!("unknown format '{}' (expected round-robin or swiss)", other)),
+    }
+
+    entries.sort_by(|a, b| b.rating.total_cmp(&a.rating));
+    print_leaderboard(&entries);
+
+    if let Some(path) = options.get_str("csv") {
+        write_csv(&entries, path)?;
+        println!("\nLeaderboard written to {}", path);
+    }
+
+    Ok(())
+}
+
+// Play one table and update ratings game by game, in game order
+fn play_match(entries: &mut [Entry], table: &[usize], games: usize, seed: u64, threads: usize) -> Result<(), String> {
+    let config = SimConfig {
+        specs: table.iter().map(|&idx| entries[idx].spec.clone()).collect(),
+        games,
+        seed,
+        rotate: true,
+        threads,
+        start_rule: StartRule::FirstSeat,
+    };
+
+    for summary in sim::play_games(&config)? {
+        let seated: Vec<usize> = summary.seating.iter().map(|&bot_idx| table[bot_idx]).collect();
+        let winner = seated[summary.winner_seat];
+        update_ratings(entries, &seated, winner);
+    }
+
+    Ok(())
+}
+
+// Multiplayer Elo: every game counts as a win for the winner against each
+// other player and a draw between the players who did not win. Each
+// player's change is averaged over their opponents so a four-player game
+// moves ratings about as much as a two-player one.
+fn update_ratings(entries: &mut [Entry], seated: &[usize], winner: usize) {
+    let opponents = (seated.len() - 1) as f64;
+    let deltas: Vec<f64> = seated
+        .iter()
+        .map(|&i| {
+            let mut delta = 0.0;
+            for &j in seated.iter().filter(|&&j| j != i) {
+                let expected = 1.0 / (1.0 + 10f64.powf((entries[j].rating - entries[i].rating) / 400.0));
+                let actual = if i == winner {
+                    1.0
+                } else if j == winner {
+                    0.0
+                } else {
+                    0.5
+                };
+                delta += actual - expected;
+            }
+            K_FACTOR * delta / opponents
+        })
+        .collect();
+
+    for (&i, delta) in seated.iter().zip(deltas) {
+        entries[i].rating += delta;
+        entries[i].games += 1;
+        if i == winner {
+            entries[i].wins += 1;
+        }
+    }
+}
+
+// All ways to pick `size` of `count` bots, in lexicographic order
+fn combinations(count: usize, size: usize) -> Vec<Vec<usize>> {
+    let mut result = Vec::new();
+    let mut current = Vec::new();
+
+    fn extend(start: usize, count: usize, size: usize, current: &mut Vec<usize>, result: &mut Vec<Vec<usize>>) {
+        if current.len() == size {
+            result.push(current.clone());
+            return;
+        }
+        for i in start..count {
+            current.push(i);
+            extend(i + 1, count, size, current, result);
+            current.pop();
+        }
+    }
+
+    extend(0, count, size, &mut current, &mut result);
+    result
+}
+
+// Seat bots of similar rating together, every table full. Bots that do not
+// make up a full table sit the round out: the lowest rated of those that
+// have had the fewest byes so far.
+fn swiss_tables(entries: &[Entry], players: usize) -> Vec<Vec<usize>> {
+    let mut by_byes: Vec<usize> = (0..entries.len()).collect();
+    by_byes.sort_by(|&a, &b| {
+        entries[a].byes.cmp(&entries[b].byes).then(entries[a].rating.total_cmp(&entries[b].rating))
+    });
+    let byes = &by_byes[..entries.len() % players];
+
+    let mut order: Vec<usize> = (0..entries.len()).filter(|idx| !byes.contains(idx)).collect();
+    order.sort_by(|&a, &b| entries[b].rating.total_cmp(&entries[a].rating));
+    order.chunks(players).map(|table| table.to_vec()).collect()
+}
+
+// Presets keep their name, weights files and engines are shown by file name
+fn display_name(spec: &str) -> String {
+    let path = match spec.strip_prefix("engine:") {
+        Some(command) => command.split_whitespace().last().unwrap_or(command),
+        None => spec,
+    };
+    Path::new(path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| spec.to_string())
+}
+
+fn print_leaderboard(entries: &[Entry]) {
+    let width = entries.iter().map(|entry| entry.name.len()).max().unwrap_or(0).max(3);
+
+    println!("\n{:>4}  {:<width$}  {:>7}  {:>6}  {:>6}  {:>7}", "Rank", "Bot", "Rating", "Games", "Wins", "Win %");
+    for (rank, entry) in entries.iter().enumerate() {
+        println!(
+            "{:>4}  {:<width$}  {:>7.1}  {:>6}  {:>6}  {:>6.1}%",
+            rank + 1,
+            entry.name,
+            entry.rating,
+            entry.games,
+            entry.wins,
+            100.0 * entry.wins as f64 / entry.games.max(1) as f64
+        );
+    }
+}
+
+fn write_csv(entries: &[Entry], path: &str) -> Result<(), String> {
+    let mut csv = String::from("rank,bot,spec,rating,games,wins,win_rate\n");
+    for (rank, entry) in entries.iter().enumerate() {
+        csv.push_str(&format!(
+            "{},{},{},{:.1},{},{},{:.4}\n",
+            rank + 1,
+            csv_field(&entry.name),
+            csv_field(&entry.spec),
+            entry.rating,
+            entry.games,
+            entry.wins,
+            entry.wins as f64 / entry.games.max(1) as f64
+        ));
+    }
+    fs::write(path, csv).map_err(|err| format!("cannot write '{}': {}", path, err))
+}
+
+// Quote a CSV field if it holds a comma, quote or line break
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(ratings: &[f64]) -> Vec<Entry> {
+        ratings
+            .iter()
+            .enumerate()
+            .map(|(idx, &rating)| Entry {
+                name: format!("bot{}", idx),
+                spec: "balanced".to_string(),
+                rating,
+                games: 0,
+                wins: 0,
+                byes: 0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn winners_gain_what_the_table_loses() {
+        let mut two = entries(&[INITIAL_RATING, INITIAL_RATING]);
+        update_ratings(&mut two, &[0, 1], 1);
+        assert_eq!(two[1].rating, INITIAL_RATING + K_FACTOR / 2.0);
+        assert_eq!(two[0].rating, INITIAL_RATING - K_FACTOR / 2.0);
+        assert_eq!((two[1].games, two[1].wins, two[0].games, two[0].wins), (1, 1, 1, 0));
+
+        // Equal losers draw among themselves, so they lose the same amount
+        let mut four = entries(&[1500.0, 1500.0, 1500.0, 1500.0]);
+        update_ratings(&mut four, &[3, 1, 0, 2], 0);
+        assert!((four[0].rating - (1500.0 + K_FACTOR / 2.0)).abs() < 1e-9);
+        for loser in &four[1..] {
+            assert!((loser.rating - (1500.0 - K_FACTOR / 6.0)).abs() < 1e-9);
+        }
+
+        // Beating a stronger bot is worth more than beating a weaker one
+        let mut upset = entries(&[1700.0, 1300.0]);
+        update_ratings(&mut upset, &[0, 1], 1);
+        let mut expected = entries(&[1700.0, 1300.0]);
+        update_ratings(&mut expected, &[0, 1], 0);
+        assert!(upset[1].rating - 1300.0 > expected[0].rating - 1700.0);
+        let total: f64 = upset.iter().map(|entry| entry.rating).sum();
+        assert!((total - 3000.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn combinations_list_every_table_once() {
+        assert_eq!(combinations(4, 2), [[0, 1], [0, 2], [0, 3], [1, 2], [1, 3], [2, 3]]);
+        assert_eq!(combinations(3, 3), [[0, 1, 2]]);
+        assert_eq!(combinations(6, 4).len(), 15);
+        assert!(combinations(6, 4).iter().all(|table| table.windows(2).all(|pair| pair[0] < pair[1])));
+    }
+
+    #[test]
+    fn swiss_tables_are_full_and_byes_go_round() {
+        let mut field = entries(&[1400.0, 1600.0, 1500.0, 1550.0, 1450.0, 1650.0, 1350.0]);
+        let tables = swiss_tables(&field, 3);
+        assert_eq!(tables, [[5, 1, 3], [2, 4, 0]]);
+
+        field[6].byes = 1;
+        assert_eq!(swiss_tables(&field, 3), [[5, 1, 3], [2, 4, 6]]);
+        assert_eq!(swiss_tables(&field, 2).len(), 3);
+        assert!(swiss_tables(&field, 2).iter().all(|table| table.len() == 2 && !table.contains(&0)));
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_when_needed() {
+        assert_eq!(csv_field("balanced"), "balanced");
+        assert_eq!(csv_field("engine:my,bot"), "\"engine:my,bot\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
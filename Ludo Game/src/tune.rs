@@ -0,0 +1,132 @@
+use crate::bot::{HeuristicBot, PlayerController};
+use crate::cli::Options;
+use crate::dice::derive_seed;
+use crate::eval::{Feature, Weights};
+use crate::game::Game;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+// Tune bot weights by self-play with SPSA (simultaneous perturbation
+// stochastic approximation): each iteration nudges every weight up or down
+// at random, plays the two perturbed bots against each other and steps
+// towards whichever side won more often.
+pub fn run(options: &Options) -> Result<(), String> {
+    let start_spec = options.get_str("start").unwrap_or("balanced");
+    let start = Weights::load(start_spec)?;
+    let iterations = options.get_or("iterations", 100usize)?;
+    let games = options.get_or("games", 40usize)?;
+    let eval_games = options.get_or("eval-games", 1000usize)?;
+    let players = options.get_or("players", 2usize)?;
+    let seed = options.get_or("seed", 1u64)?;
+    let step = options.get_or("step", 0.5f64)?;
+    let perturbation = options.get_or("perturbation", 0.2f64)?;
+    let out = options.get_str("out").unwrap_or("tuned.cfg");
+
+    if !(2..=4).contains(&players) {
+        return Err("--players must be between 2 and 4".to_string());
+    }
+    if games == 0 || eval_games == 0 {
+        return Err("--games and --eval-games must be at least 1".to_string());
+    }
+
+    // Weights are tuned relative to their starting size so small and large
+    // weights move at a comparable pace
+    let scale: Vec<f64> = Feature::ALL.iter().map(|&f| start.get(f).abs().max(0.5)).collect();
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut current = start.clone();
+    // Iterates are checked against the starting weights every 10 iterations,
+    // all on the same dice, and the best one is kept. The starting weights
+    // themselves win their share and no more.
+    let checkpoint_seed = derive_seed(seed, u64::MAX - 1);
+    let mut best = start.clone();
+    let mut best_rate = 1.0 / players as f64;
+
+    println!("Tuning from '{}' for {} iterations of {} games", start_spec, iterations, games);
+
+    for k in 0..iterations {
+        let a_k = step / (k as f64 + 1.0 + iterations as f64 / 10.0).powf(0.602);
+        let c_k = perturbation / (k as f64 + 1.0).powf(0.101);
+        let delta: Vec<f64> = Feature::ALL.iter().map(|_| if rng.gen::<bool>() { 1.0 } else { -1.0 }).collect();
+
+        let mut plus = current.clone();
+        let mut minus = current.clone();
+        for (i, &feature) in Feature::ALL.iter().enumerate() {
+            plus.set(feature, current.get(feature) + c_k * scale[i] * delta[i]);
+            minus.set(feature, current.get(feature) - c_k * scale[i] * delta[i]);
+        }
+
+        let iteration_seed = derive_seed(seed, k as u64);
+        let plus_rate = win_rate(&plus, &minus, players, games, iteration_seed);
+        let minus_rate = (1.0 - plus_rate) / (players - 1) as f64;
+
+        // plus_rate - minus_rate, divided by the size of the perturbation
+        for (i, &feature) in Feature::ALL.iter().enumerate() {
+            let gradient = (plus_rate - minus_rate) / (2.0 * c_k * delta[i]);
+            current.set(feature, current.get(feature) + a_k * scale[i] * gradient);
+        }
+
+        if (k + 1) % 10 == 0 || k + 1 == iterations {
+            let rate = win_rate(&current, &start, players, games, checkpoint_seed);
+            println!("  iteration {:4}: {} ({:.1}% against the start)", k + 1, current.to_line(), rate * 100.0);
+            if rate > best_rate {
+                best = current.clone();
+                best_rate = rate;
+            }
+        }
+    }
+
+    println!("\nEvaluating the best weights against '{}' over {} games...", start_spec, eval_games);
+    println!("  {}", best.to_line());
+    let rate = win_rate(&best, &start, players, eval_games, derive_seed(seed, u64::MAX));
+    let baseline = 1.0 / players as f64;
+    let margin = 1.96 * (rate * (1.0 - rate) / eval_games as f64).sqrt();
+    println!(
+        "Tuned win rate: {:.1}% ± {:.1}% (95% CI), expected {:.1}% for equal bots",
+        rate * 100.0,
+        margin * 100.0,
+        baseline * 100.0
+    );
+    println!(
+        "Improvement: {:+.1} percentage points (95% CI {:+.1} to {:+.1})",
+        (rate - baseline) * 100.0,
+        (rate - baseline - margin) * 100.0,
+        (rate - baseline + margin) * 100.0
+    );
+
+    let comment = format!(
+        "Tuned from '{}' by {} SPSA iterations; {:.1}% ± {:.1}% against it with {} players",
+        start_spec,
+        iterations,
+        rate * 100.0,
+        margin * 100.0,
+        players
+    );
+    best.save(out, &comment)?;
+    println!("Best weights written to {}", out);
+
+    Ok(())
+}
+
+// Fraction of games `candidate` wins against copies of `opponent`. The
+// candidate takes every seat in turn so no one profits from sitting first.
+fn win_rate(candidate: &Weights, opponent: &Weights, players: usize, games: usize, seed: u64) -> f64 {
+    let mut wins = 0;
+
+    for game_idx in 0..games {
+        let candidate_seat = game_idx % players;
+        let bots: Vec<Box<dyn PlayerController>> = (0..players)
+            .map(|seat| {
+                let weights = if seat == candidate_seat { candidate } else { opponent };
+                Box::new(HeuristicBot::new(format!("Bot {}", seat + 1), weights.clone())) as Box<dyn PlayerController>
+            })
+            .collect();
+
+        // Games that only differ in seating share their dice
+        let mut game = Game::headless(bots, derive_seed(seed, (game_idx / players) as u64));
+        if game.start() == Some(candidate_seat) {
+            wins += 1;
+        }
+    }
+
+    wins as f64 / games as f64
+}
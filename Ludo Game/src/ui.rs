@@ -1,33 +1,115 @@
+use crate::advisor::Hint;
+use crate::analysis::AnalysisReport;
 use crate::board::Board;
 use crate::player::{Player, PlayerId};
+use crate::save::DEFAULT_SAVE_PATH;
 use colored::{Color, Colorize};
 use std::collections::HashMap;
 use std::io::{self, Write};
 
+// What a player typed when asked which piece to move
+pub enum PieceChoice {
+    Piece(usize),
+    Hint,
+    Quit,
+}
+
+// What a player typed when asked to roll
+pub enum RollChoice {
+    Roll,
+    Undo,
+    Redo,
+    Save,
+    Quit,
+}
+
+// What to do when a player quits in the middle of a game
+pub enum QuitChoice {
+    Save,        // Save the game, then stop
+    Resign,      // Drop out and let the others play on
+    Abandon,     // Stop without saving
+    KeepPlaying,
+}
+
+// Where to go next while replaying a recorded game
+pub enum ReplayCommand {
+    Forward,
+    Back,
+    Jump(usize), // To the position after this entry, 0 for the start
+    Variation(usize), // Counting from 1, in the order they appear in the record
+    MainLine,
+    Branch,
+    Quit,
+}
+
+// What to do next in a what-if branch
+pub enum BranchCommand {
+    Roll(Option<u8>), // A forced dice value, or None to roll
+    Bots(String),     // Let bots made from this spec play the rest
+    Keep,
+    Drop,
+}
+
+// The player typed `q` or `quit`, or input ran out
+#[derive(Debug)]
+pub struct Quit;
+
 pub struct UI {
     input_buffer: String,
+    silent: bool, // true for headless games: nothing is printed
+    closed: bool, // Standard input has ended, so every prompt quits
 }
 
 impl UI {
     pub fn new() -> Self {
         UI {
             input_buffer: String::new(),
+            silent: false,
+            closed: false,
         }
     }
     
-    fn get_input(&mut self) -> String {
+    // UI for games played entirely by bots, which never prints or reads anything
+    pub fn silent() -> Self {
+        UI {
+            input_buffer: String::new(),
+            silent: true,
+            closed: false,
+        }
+    }
+    
+    // A line of input, or Quit if the player asked to quit. It is up to
+    // the caller what quitting means at that point.
+    fn get_input(&mut self) -> Result<String, Quit> {
+        if self.closed {
+            return Err(Quit);
+        }
         self.input_buffer.clear();
         io::stdout().flush().unwrap();
-        io::stdin().read_line(&mut self.input_buffer).unwrap();
+        match io::stdin().read_line(&mut self.input_buffer) {
+            Ok(0) | Err(_) => {
+                println!();
+                self.closed = true;
+                return Err(Quit);
+            }
+            Ok(_) => {}
+        }
         let input = self.input_buffer.trim().to_string();
         if input.eq_ignore_ascii_case("q") || input.eq_ignore_ascii_case("quit") {
-            println!("Quitting game. Goodbye!");
-            std::process::exit(0);
+            return Err(Quit);
         }
-        input
+        Ok(input)
+    }
+    
+    // Whether input has ended, e.g. because the terminal was closed
+    pub fn input_closed(&self) -> bool {
+        self.closed
     }
     
     pub fn display_welcome(&self) {
+        if self.silent {
+            return;
+        }
         println!("{}", "Welcome to Ludo Game!".bold().green());
         println!("Get all your pieces from the yard to the finish line.");
         println!("Roll a 6 to move a piece out of the yard.");
@@ -35,49 +117,161 @@ impl UI {
         println!("Roll a 6 or capture to get an extra turn.\n");
     }
     
-    pub fn get_player_count(&mut self) -> usize {
+    pub fn get_player_count(&mut self) -> Result<usize, Quit> {
         loop {
             print!("Enter the number of players (2-4): ");
-            let input = self.get_input();
+            let input = self.get_input()?;
             
             match input.parse::<usize>() {
-                Ok(count) if count >= 2 && count <= 4 => return count,
+                Ok(count) if (2..=4).contains(&count) => return Ok(count),
                 _ => println!("Please enter a number between 2 and 4."),
             }
         }
     }
     
-    pub fn get_player_name(&mut self, player_id: PlayerId) -> String {
+    pub fn get_player_name(&mut self, player_id: PlayerId) -> Result<String, Quit> {
         print!("Enter name for Player {}: ", player_id + 1);
-        let name = self.get_input();
+        let name = self.get_input()?;
         
         if name.is_empty() {
-            format!("Player {}", player_id + 1)
+            Ok(format!("Player {}", player_id + 1))
+        } else {
+            Ok(name)
+        }
+    }
+    
+    // The bot spec, or None for a human player
+    pub fn get_bot_personality(&mut self, player_id: PlayerId) -> Result<Option<String>, Quit> {
+        print!("Bot personality for Player {} (balanced, aggressive, defensive, racing, a weights file or engine:COMMAND; blank for human): ", player_id + 1);
+        let spec = self.get_input()?;
+        
+        if spec.is_empty() {
+            Ok(None)
         } else {
-            name
+            Ok(Some(spec))
         }
     }
     
-    pub fn display_board(&self, board: &Board, player_colors: &HashMap<PlayerId, Color>) {
-        println!("{}", board.render(player_colors));
+    // Yes/no question; anything but y/yes counts as no, and so does quitting
+    pub fn confirm(&mut self, question: &str) -> bool {
+        if self.silent {
+            return false;
+        }
+        print!("{}", question);
+        match self.get_input() {
+            Ok(answer) => answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes"),
+            Err(Quit) => false,
+        }
+    }
+    
+    pub fn choose_quit_action(&mut self, player: &Player) -> QuitChoice {
+        println!("\n{}", "Quit the game?".bold());
+        println!("1. Save the game and quit");
+        println!("2. Resign {} and let the others play on", player.name.color(player.color));
+        println!("3. Abandon the game without saving");
+        println!("4. Keep playing");
+        
+        loop {
+            print!("Enter choice (1-4): ");
+            match self.get_input() {
+                Ok(input) => match input.as_str() {
+                    "1" => return QuitChoice::Save,
+                    "2" => return QuitChoice::Resign,
+                    "3" => return QuitChoice::Abandon,
+                    "4" => return QuitChoice::KeepPlaying,
+                    _ => println!("Invalid choice. Please try again."),
+                },
+                // With no more input, just stop; the game keeps its autosave
+                Err(Quit) if self.closed => return QuitChoice::Abandon,
+                Err(Quit) => println!("Pick one of the choices, or 4 to keep playing."),
+            }
+        }
+    }
+    
+    pub fn display_message(&self, message: &str) {
+        if self.silent {
+            return;
+        }
+        println!("{}", message);
+    }
+    
+    pub fn display_error(&self, message: &str) {
+        if self.silent {
+            return;
+        }
+        println!("{}", message.red());
+    }
+    
+    pub fn display_roll_off_roll(&self, player: &Player, value: u8) {
+        if self.silent {
+            return;
+        }
+        println!("{} rolls {} for the start", player.name.color(player.color), value);
+    }
+    
+    pub fn display_first_player(&self, player: &Player) {
+        if self.silent {
+            return;
+        }
+        println!("{} rolled highest and goes first!\n", player.name.color(player.color).bold());
+    }
+    
+    pub fn display_board(
+        &self,
+        board: &Board,
+        player_colors: &HashMap<PlayerId, Color>,
+        viewer: PlayerId,
+        win_probabilities: Option<&[f64]>,
+    ) {
+        if self.silent {
+            return;
+        }
+        println!("{}", board.render(player_colors, viewer, win_probabilities));
     }
     
     pub fn display_player_turn(&self, player: &Player) {
+        if self.silent {
+            return;
+        }
         println!("\n{}'s turn", player.name.color(player.color).bold());
     }
     
-    pub fn prompt_for_dice_roll(&self) {
-        print!("Press Enter to roll the dice...");
-        io::stdout().flush().unwrap();
-        io::stdin().read_line(&mut String::new()).unwrap();
+    pub fn prompt_for_dice_roll(&mut self, undo_allowed: bool) -> RollChoice {
+        if undo_allowed {
+            print!("Press Enter to roll the dice (or type 'undo', 'redo', 'save' or 'quit')...");
+        } else {
+            print!("Press Enter to roll the dice (or type 'save' or 'quit')...");
+        }
+        match self.get_input() {
+            Ok(input) if input.eq_ignore_ascii_case("save") => RollChoice::Save,
+            Ok(input) if undo_allowed && input.eq_ignore_ascii_case("undo") => RollChoice::Undo,
+            Ok(input) if undo_allowed && input.eq_ignore_ascii_case("redo") => RollChoice::Redo,
+            Ok(_) => RollChoice::Roll,
+            Err(Quit) => RollChoice::Quit,
+        }
+    }
+    
+    // Where to save, or None if the player changed their mind
+    pub fn get_save_path(&mut self) -> Option<String> {
+        print!("Save to file [{}]: ", DEFAULT_SAVE_PATH);
+        let path = self.get_input().ok()?;
+        
+        if path.is_empty() {
+            Some(DEFAULT_SAVE_PATH.to_string())
+        } else {
+            Some(path)
+        }
     }
     
     pub fn display_dice_roll(&self, value: u8) {
+        if self.silent {
+            return;
+        }
         println!("You rolled a {}!", value.to_string().yellow().bold());
     }
     
-    pub fn choose_piece(&mut self, player_id: PlayerId, valid_pieces: &[usize]) -> usize {
-        println!("Choose a piece to move:");
+    pub fn choose_piece(&mut self, valid_pieces: &[usize]) -> PieceChoice {
+        println!("Choose a piece to move (or type 'hint' or 'quit'):");
         
         for (i, &piece_idx) in valid_pieces.iter().enumerate() {
             println!("{}. Piece {}", i + 1, piece_idx);
@@ -85,30 +279,117 @@ impl UI {
         
         loop {
             print!("Enter choice (1-{}): ", valid_pieces.len());
-            let input = self.get_input();
+            let input = match self.get_input() {
+                Ok(input) => input,
+                Err(Quit) => return PieceChoice::Quit,
+            };
+            
+            if input.eq_ignore_ascii_case("hint") {
+                return PieceChoice::Hint;
+            }
             
             match input.parse::<usize>() {
-                Ok(choice) if choice >= 1 && choice <= valid_pieces.len() => {
-                    return valid_pieces[choice - 1];
+                Ok(choice) if (1..=valid_pieces.len()).contains(&choice) => {
+                    return PieceChoice::Piece(valid_pieces[choice - 1]);
                 }
                 _ => println!("Invalid choice. Please try again."),
             }
         }
     }
     
+    pub fn display_hint(&self, hint: &Hint) {
+        if self.silent {
+            return;
+        }
+        println!("{} Move piece {}: {}.", "Hint:".cyan().bold(), hint.piece_idx, hint.explanation);
+        
+        if hint.margins.is_empty() {
+            println!("It is the only legal move.");
+        }
+        for &(piece_idx, margin) in &hint.margins {
+            if hint.exact {
+                println!("  {:.1}% more likely to win than moving piece {}", margin * 100.0, piece_idx);
+            } else {
+                println!("  {:.2} points better than moving piece {}", margin, piece_idx);
+            }
+        }
+        if hint.exact {
+            println!("  (exact values from the endgame tablebase)");
+        }
+    }
+    
+    pub fn display_bot_choice(&self, bot_name: &str, piece_idx: usize) {
+        if self.silent {
+            return;
+        }
+        println!("{} moves piece {}", bot_name.italic(), piece_idx);
+    }
+    
     pub fn display_move_result(&self, player_id: PlayerId, piece_idx: usize, message: &str) {
+        if self.silent {
+            return;
+        }
         println!("Player {}'s piece {} {}", player_id, piece_idx, message);
     }
     
     pub fn display_no_valid_moves(&self) {
+        if self.silent {
+            return;
+        }
         println!("{}", "No valid moves available. Turn skipped.".yellow());
     }
     
     pub fn display_extra_turn(&self) {
+        if self.silent {
+            return;
+        }
         println!("{}", "You get an extra turn!".green());
     }
     
+    pub fn display_analysis(&self, report: &AnalysisReport, players: &[Player]) {
+        if self.silent {
+            return;
+        }
+        println!("\n{}", "=== GAME ANALYSIS ===".bold());
+        
+        let blunders: Vec<_> = report.turns.iter().filter(|review| review.is_blunder()).collect();
+        if blunders.is_empty() {
+            println!("No blunders found.");
+        }
+        for review in blunders {
+            let player = &players[review.player_id];
+            let (chosen, chosen_win) = review.chosen.unwrap();
+            let (best, best_win) = review.best.unwrap();
+            println!(
+                "Turn {}: {} rolled {} and moved piece {} ({:.0}% to win); piece {} was best ({:.0}%). {} -{:.0}%",
+                review.turn,
+                player.name.color(player.color),
+                review.dice_value,
+                chosen,
+                chosen_win * 100.0,
+                best,
+                best_win * 100.0,
+                "Blunder:".red().bold(),
+                review.loss() * 100.0
+            );
+        }
+        
+        println!("\nWhere each result came from (change in own win probability):");
+        for (player, summary) in players.iter().zip(&report.players) {
+            println!(
+                "  {:<16} dice luck {:+6.1}%   decisions {:+6.1}%   blunders {}",
+                player.name.color(player.color),
+                summary.luck * 100.0,
+                summary.decisions * 100.0,
+                summary.blunders
+            );
+        }
+    }
+    
     pub fn display_game_over(&self, winner: &Player) {
+        if self.silent {
+            return;
+        }
         println!("\n{}", "=== GAME OVER ===".bold());
         println!("{} {} {}", 
             "Congratulations!".green().bold(), 
@@ -116,4 +397,80 @@ impl UI {
             "has won the game!".green().bold()
         );
     }
+    
+    // What led to the position on show: the start, or the entry just played
+    pub fn display_replay_entry(&self, entry: usize, total: usize, annotation: Option<(&Player, &str)>) {
+        match annotation {
+            Some((player, annotation)) => println!(
+                "\nEntry {} of {}: {} {}",
+                entry,
+                total,
+                player.name.color(player.color).bold(),
+                annotation.yellow().bold()
+            ),
+            None if entry == 0 => println!("\nStart of the game ({} entries)", total),
+            None => println!("\nBefore entry {} ({} entries)", entry + 1, total),
+        }
+    }
+    
+    pub fn prompt_replay(&mut self) -> ReplayCommand {
+        loop {
+            print!("Enter: forward, b: back, <n>: jump to entry, v<n>: variation, m: main line, branch: what-if, q: quit > ");
+            let input = match self.get_input() {
+                Ok(input) => input,
+                Err(Quit) => return ReplayCommand::Quit,
+            };
+            
+            match input.as_str() {
+                "" | "f" => return ReplayCommand::Forward,
+                "b" => return ReplayCommand::Back,
+                "m" => return ReplayCommand::MainLine,
+                "branch" => return ReplayCommand::Branch,
+                _ => {
+                    if let Some(Ok(variation)) = input.strip_prefix('v').map(str::parse::<usize>) {
+                        return ReplayCommand::Variation(variation);
+                    }
+                    match input.parse::<usize>() {
+                        Ok(entry) => return ReplayCommand::Jump(entry),
+                        _ => println!("Invalid choice. Please try again."),
+                    }
+                }
+            }
+        }
+    }
+    
+    // Variations that replace the next entry, numbered for `v<n>`
+    pub fn display_variations(&self, entry: usize, variations: &[(usize, String)]) {
+        for (number, first_entry) in variations {
+            println!("  v{}: instead of entry {}, {}", number, entry, first_entry.cyan());
+        }
+    }
+    
+    pub fn prompt_branch(&mut self, game_over: bool) -> BranchCommand {
+        loop {
+            if game_over {
+                print!("keep: save this branch, drop: throw it away > ");
+            } else {
+                print!("Enter: roll, 1-6: force a roll, bots [personality]: let bots play on, keep: save this branch, drop: throw it away > ");
+            }
+            let input = match self.get_input() {
+                Ok(input) => input,
+                Err(Quit) => return BranchCommand::Drop,
+            };
+            
+            match input.split_whitespace().collect::<Vec<_>>()[..] {
+                ["keep"] => return BranchCommand::Keep,
+                ["drop"] => return BranchCommand::Drop,
+                _ if game_over => println!("The game is over in this branch."),
+                [] => return BranchCommand::Roll(None),
+                ["bots"] => return BranchCommand::Bots("balanced".to_string()),
+                ["bots", spec] => return BranchCommand::Bots(spec.to_string()),
+                [roll] => match roll.parse::<u8>() {
+                    Ok(value) if (1..=6).contains(&value) => return BranchCommand::Roll(Some(value)),
+                    _ => println!("Invalid choice. Please try again."),
+                },
+                _ => println!("Invalid choice. Please try again."),
+            }
+        }
+    }
 }
\ No newline at end of file